@@ -3,39 +3,29 @@ use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
-fn execute_opcode(starting_position: usize, program: &mut Vec<usize>) {
-    let operation = program[starting_position];
-    let operand_1 = program[program[starting_position + 1]];
-    let operand_2 = program[program[starting_position + 2]];
-    let destination = program[starting_position + 3];
+// The Intcode computer from Day 5, restricted to the instructions Day 2 programs know about
+#[allow(dead_code)]
+#[path = "../5/intcode.rs"]
+mod intcode;
 
-    let result = match operation {
-        1 => operand_1 + operand_2,
-        2 => operand_1 * operand_2,
-        _ => panic!("Unknown operation code: {}", operation)
-    };
+use intcode::{IntcodeComputer, Memory, Profile, Program};
 
-    program[destination] = result;
-    
-    //println!("Operation: {}, on {} {} = {}, into {}", operation, operand_1, operand_2, result, destination);
-}
-
-fn execute_program(program: &mut Vec<usize>) {
-    let mut position = 0;
-    while program[position] != 99 {
-        execute_opcode(position, program);
-        position += 4;
+fn execute_program(computer: &IntcodeComputer, program: &Program) -> Memory {
+    let mut memory = Memory::initialize(program);
+    if let Err(why) = computer.resume(&mut memory, &mut [].iter(), &mut Vec::new()) {
+        panic!("{}", why);
     }
+    return memory;
 }
 
-fn parse_program(line: &String) -> Vec<usize> {
+fn parse_program(line: &String) -> Program {
     return line.split(",")
-               .map(|opcode| opcode.parse::<usize>())
+               .map(|opcode| opcode.trim().parse::<i32>())
                .filter_map(Result::ok)
                .collect();
 }
 
-fn parse_file(file_name: &Path) -> Vec<usize> {
+fn parse_file(file_name: &Path) -> Program {
     let file = match File::open(&file_name) {
         Err(why) => panic!("Couldn't open {}: {}", file_name.display(), why),
         Ok(file) => file
@@ -50,7 +40,10 @@ fn parse_file(file_name: &Path) -> Vec<usize> {
     return parse_program(&line);
 }
 
-fn find_answer(program: &Vec<usize>, answer: usize) -> Result<(usize, usize), &str> {
+fn find_answer(program: &Program, answer: i32) -> Result<(i32, i32), &str> {
+    let mut computer = IntcodeComputer::with_profile(Profile::Basic);
+    computer.set_trace(false);
+
     for noun in 0..100 {
         for verb in 0..100 {
             let mut program_instance = program.to_vec();
@@ -58,10 +51,10 @@ fn find_answer(program: &Vec<usize>, answer: usize) -> Result<(usize, usize), &s
             //Fix up error
             program_instance[1] = noun;
             program_instance[2] = verb;    
-            execute_program(&mut program_instance);
-            println!("Noun: {}, Verb: {} produces {}", noun, verb, program_instance[0]);
+            let memory = execute_program(&computer, &program_instance);
+            println!("Noun: {}, Verb: {} produces {}", noun, verb, memory.values[0]);
             
-            if program_instance[0] == answer {
+            if memory.values[0] == answer {
                 return Ok((noun, verb));
             }
         }
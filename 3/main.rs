@@ -6,20 +6,27 @@ use std::path::Path;
 use std::cmp;
 use std::fmt;
 
+// The Point and Direction the Day 5 robot and maze use
+#[allow(dead_code)]
+#[path = "../5/grid.rs"]
+mod grid;
+
+use grid::{Direction, Point};
+
 fn overlap(start_a: i32, end_a: i32, start_b: i32, end_b:i32) -> bool {
     return start_a <= end_b && start_b <= end_a;
 }
 
+// A point on a wire, and how many steps along the wire it is
 #[derive(Clone)]
-struct Point {
-    x: i32,
-    y: i32,
+struct WirePoint {
+    position: Point,
     steps: i32
 }
 
-impl Point {
-    fn new(x: i32, y: i32, steps: i32) -> Point{
-        return Point {x, y, steps};
+impl WirePoint {
+    fn new(x: i32, y: i32, steps: i32) -> WirePoint {
+        return WirePoint {position: Point::new(x, y), steps};
     }
 
     // fn manhattan_distance(&self) -> i32 {
@@ -27,32 +34,32 @@ impl Point {
     // }
 }
 
-impl ops::Add<Point> for Point {
-    type Output = Point;
-    fn add(self, other: Point) -> Point {
-        return Point::new(self.x + other.x, self.y + other.y, self.steps + other.steps);
+impl ops::Add<WirePoint> for WirePoint {
+    type Output = WirePoint;
+    fn add(self, other: WirePoint) -> WirePoint {
+        return WirePoint {position: self.position + other.position, steps: self.steps + other.steps};
     }
 }
 
-impl fmt::Display for Point {
+impl fmt::Display for WirePoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {}) ({} steps)", self.x, self.y, self.steps)
+        write!(f, "{} ({} steps)", self.position, self.steps)
     }
 }
 
-impl PartialEq for Point {
-    fn eq(&self, other: &Point) -> bool {
-        return self.x == other.x && self.y == other.y;
+impl PartialEq for WirePoint {
+    fn eq(&self, other: &WirePoint) -> bool {
+        return self.position == other.position;
     }
 }
 
 struct Intersection {
-    first_point: Point,
-    second_point: Point
+    first_point: WirePoint,
+    second_point: WirePoint
 }
 
 impl Intersection {
-    fn new(first_point: Point, second_point: Point) -> Intersection {
+    fn new(first_point: WirePoint, second_point: WirePoint) -> Intersection {
         return Intersection {first_point, second_point}
     }
 
@@ -61,45 +68,33 @@ impl Intersection {
     }
 }
 
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right
-}
-
 struct WireSegment {
-    start: Point,
-    end: Point
+    start: WirePoint,
+    end: WirePoint
 }
 
 impl WireSegment {
-    fn new(start: Point, end: Point) -> WireSegment {
+    fn new(start: WirePoint, end: WirePoint) -> WireSegment {
         return WireSegment {start, end};
     }
 
-    fn from(start: Point, direction: Direction, distance: i32) -> WireSegment {
-        let delta = match direction {
-            Direction::Up => Point::new(0, distance, distance),
-            Direction::Down => Point::new(0, -distance, distance),
-            Direction::Left => Point::new(-distance, 0, distance),
-            Direction::Right => Point::new(distance, 0, distance)
-        };
+    fn from(start: WirePoint, direction: Direction, distance: i32) -> WireSegment {
+        let delta = WirePoint {position: direction.delta() * distance, steps: distance};
 
         let end = start.clone() + delta;
         return WireSegment::new(start, end);
     }
 
     fn intersects(&self, other: &WireSegment) -> Option<Intersection> {
-        let self_max_x = cmp::max(self.start.x, self.end.x);
-        let self_min_x = cmp::min(self.start.x, self.end.x);
-        let self_max_y = cmp::max(self.start.y, self.end.y);
-        let self_min_y = cmp::min(self.start.y, self.end.y);
+        let self_max_x = cmp::max(self.start.position.x, self.end.position.x);
+        let self_min_x = cmp::min(self.start.position.x, self.end.position.x);
+        let self_max_y = cmp::max(self.start.position.y, self.end.position.y);
+        let self_min_y = cmp::min(self.start.position.y, self.end.position.y);
 
-        let other_max_x = cmp::max(other.start.x, other.end.x);
-        let other_min_x = cmp::min(other.start.x, other.end.x);
-        let other_max_y = cmp::max(other.start.y, other.end.y);
-        let other_min_y = cmp::min(other.start.y, other.end.y);
+        let other_max_x = cmp::max(other.start.position.x, other.end.position.x);
+        let other_min_x = cmp::min(other.start.position.x, other.end.position.x);
+        let other_max_y = cmp::max(other.start.position.y, other.end.position.y);
+        let other_min_y = cmp::min(other.start.position.y, other.end.position.y);
 
         if overlap(self_min_x, self_max_x, other_min_x, other_max_x) &&
                overlap(self_min_y, self_max_y, other_min_y, other_max_y) {
@@ -113,19 +108,19 @@ impl WireSegment {
             } else if self.end == other.end {
                 return Some(Intersection::new(self.end.clone(), other.end.clone()));
             } else if self_min_x == self_max_x {
-                let self_distance = (other_min_y - self.start.y).abs();
-                let self_intersection = Point::new(self_min_x, other_min_y, self.start.steps + self_distance);
+                let self_distance = (other_min_y - self.start.position.y).abs();
+                let self_intersection = WirePoint::new(self_min_x, other_min_y, self.start.steps + self_distance);
 
-                let other_distance = (self_min_x - other.start.x).abs();
-                let other_intersection = Point::new(self_min_x, other_min_y, other.start.steps + other_distance);
+                let other_distance = (self_min_x - other.start.position.x).abs();
+                let other_intersection = WirePoint::new(self_min_x, other_min_y, other.start.steps + other_distance);
 
                 return Some(Intersection::new(self_intersection, other_intersection));
             } else {
-                let self_distance = (other_min_x - self.start.x).abs();
-                let self_intersection = Point::new(self_min_x, other_min_y, self.start.steps + self_distance);
+                let self_distance = (other_min_x - self.start.position.x).abs();
+                let self_intersection = WirePoint::new(self_min_x, other_min_y, self.start.steps + self_distance);
 
-                let other_distance = (self_min_y - other.start.y).abs();
-                let other_intersection = Point::new(self_min_x, other_min_y, other.start.steps + other_distance);
+                let other_distance = (self_min_y - other.start.position.y).abs();
+                let other_intersection = WirePoint::new(self_min_x, other_min_y, other.start.steps + other_distance);
 
                 return Some(Intersection::new(self_intersection, other_intersection));
             }
@@ -142,7 +137,7 @@ impl fmt::Display for WireSegment {
     }
 }
 
-fn parse_wire_segment(start: Point, description: &str) -> WireSegment {
+fn parse_wire_segment(start: WirePoint, description: &str) -> WireSegment {
     let direction_char = description.chars().nth(0).unwrap();
     let direction = match direction_char {
         'U' => Direction::Up,
@@ -157,7 +152,7 @@ fn parse_wire_segment(start: Point, description: &str) -> WireSegment {
 
 fn parse_wire(line: &String) -> Result<Vec<WireSegment>, std::io::Error> {
     return Ok(line.split(",")
-               .scan(Point::new(0, 0, 0), |state, description| {
+               .scan(WirePoint::new(0, 0, 0), |state, description| {
                    let segment = parse_wire_segment(state.clone(), &description);
                    *state = segment.end.clone();
                    return Some(segment);
@@ -0,0 +1,381 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::disassembly::ControlFlow;
+use super::intcode::{Instruction, ParameterMode, HOST_CALL};
+use super::{IntcodeComputer, Program};
+
+// Sets bigger than this are given up on, which is what stops loops being followed forever
+const MAX_VALUES: usize = 16;
+
+// The values a cell could hold at some point in the program
+#[derive(Clone, Debug, PartialEq)]
+pub enum Values {
+    Set(BTreeSet<i32>),
+    Any
+}
+
+impl Values {
+    fn one(value: i32) -> Values {
+        return Values::Set(vec![value].into_iter().collect());
+    }
+
+    fn from_set(values: BTreeSet<i32>) -> Values {
+        return if values.len() > MAX_VALUES { Values::Any } else { Values::Set(values) };
+    }
+
+    fn union(&self, other: &Values) -> Values {
+        return match (self, other) {
+            (Values::Set(a), Values::Set(b)) => Values::from_set(a.union(b).cloned().collect()),
+            _ => Values::Any
+        };
+    }
+
+    // Every result of applying the operation to a value from each, or Any if one overflows
+    fn combine(&self, other: &Values, operation: fn(i32, i32) -> Option<i32>) -> Values {
+        let (a, b) = match (self, other) {
+            (Values::Set(a), Values::Set(b)) => (a, b),
+            _ => return Values::Any
+        };
+
+        let mut results = BTreeSet::new();
+        for &x in a {
+            for &y in b {
+                match operation(x, y) {
+                    None => return Values::Any,
+                    Some(result) => results.insert(result)
+                };
+            }
+        }
+        return Values::from_set(results);
+    }
+
+    fn may_be(&self, predicate: impl Fn(i32) -> bool) -> bool {
+        return match self {
+            Values::Set(values) => values.iter().any(|&value| predicate(value)),
+            Values::Any => true
+        };
+    }
+}
+
+impl fmt::Display for Values {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Values::Set(values) => {
+                let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                write!(f, "{{{}}}", values.join(", "))
+            },
+            Values::Any => write!(f, "anything")
+        }
+    }
+}
+
+// What memory could hold just before an instruction runs. Cells not listed hold what the program
+// started with.
+#[derive(Clone, PartialEq)]
+struct State {
+    cells: BTreeMap<usize, Values>,
+    relative_base: Values
+}
+
+impl State {
+    fn cell(&self, program: &Program, address: usize) -> Values {
+        return match self.cells.get(&address) {
+            Some(values) => values.clone(),
+            None => Values::one(program.get(address).cloned().unwrap_or(0))
+        };
+    }
+
+    // Adds in what another path to the same instruction could leave in memory, returning whether
+    // that changed anything
+    fn join(&mut self, other: &State, program: &Program) -> bool {
+        let mut joined = self.clone();
+        for &address in self.cells.keys().chain(other.cells.keys()) {
+            joined.cells.insert(address, self.cell(program, address).union(&other.cell(program, address)));
+        }
+        joined.relative_base = self.relative_base.union(&other.relative_base);
+
+        if joined == *self {
+            return false;
+        }
+        *self = joined;
+        return true;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Firing {
+    Always,
+    Never,
+    Sometimes
+}
+
+// What a program can do, worked out without running it. Only meaningful if complete, since
+// otherwise the program did something that couldn't be followed, like running a cell that could
+// hold anything or writing through a pointer that could point anywhere.
+pub struct Analysis {
+    pub complete: bool,
+    // Every instruction that can run
+    pub reachable: BTreeSet<usize>,
+    // Every cell that can be written, and what can be written to it
+    pub written: BTreeMap<usize, Values>,
+    pub jumps: BTreeMap<usize, Firing>,
+    // Instructions that stop the computer with an error: an unknown opcode or parameter mode, or a
+    // negative address
+    pub faults: BTreeSet<usize>
+}
+
+impl Analysis {
+    // Instructions found by following every jump that can't run once the values are taken into account
+    pub fn unreachable(&self, control_flow: &ControlFlow) -> Vec<usize> {
+        return control_flow.instructions.keys().filter(|address| !self.reachable.contains(address)).cloned().collect();
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.complete {
+            writeln!(f, "Analysis incomplete: the program does something that can't be followed")?;
+        }
+        writeln!(f, "{} reachable instructions", self.reachable.len())?;
+        for (address, firing) in &self.jumps {
+            match firing {
+                Firing::Always => writeln!(f, "{:>5}: jump always fires", address)?,
+                Firing::Never => writeln!(f, "{:>5}: jump never fires", address)?,
+                Firing::Sometimes => ()
+            };
+        }
+        for address in &self.faults {
+            writeln!(f, "{:>5}: the computer can stop with an error here", address)?;
+        }
+        writeln!(f, "{} cells can be written:", self.written.len())?;
+        for (address, values) in &self.written {
+            writeln!(f, "{:>5}: {}", address, values)?;
+        }
+        return Ok(());
+    }
+}
+
+struct Analyser<'a> {
+    computer: &'a IntcodeComputer,
+    program: &'a Program,
+    input: Values,
+    states: BTreeMap<usize, State>,
+    worklist: Vec<usize>,
+    fired: BTreeMap<usize, (bool, bool)>,
+    analysis: Analysis
+}
+
+impl<'a> Analyser<'a> {
+    fn flow(&mut self, address: usize, state: &State) {
+        let changed = match self.states.get_mut(&address) {
+            None => {
+                self.states.insert(address, state.clone());
+                true
+            },
+            Some(existing) => existing.join(state, self.program)
+        };
+        if changed {
+            self.worklist.push(address);
+        }
+    }
+
+    // Where a parameter points, or None for an immediate read
+    fn address(&self, state: &State, mode: ParameterMode, parameter: &Values) -> Option<Values> {
+        return match mode {
+            ParameterMode::Immediate => None,
+            ParameterMode::Position => Some(parameter.clone()),
+            ParameterMode::Relative => Some(state.relative_base.combine(parameter, i32::checked_add))
+        };
+    }
+
+    fn read(&mut self, at: usize, state: &State, mode: ParameterMode, parameter: &Values) -> Values {
+        let addresses = match self.address(state, mode, parameter) {
+            None => return parameter.clone(),
+            Some(Values::Any) => return Values::Any,
+            Some(Values::Set(addresses)) => addresses
+        };
+
+        let mut values = Values::Set(BTreeSet::new());
+        for address in addresses {
+            if address < 0 {
+                self.analysis.faults.insert(at);
+            } else {
+                values = values.union(&state.cell(self.program, address as usize));
+            }
+        }
+        return values;
+    }
+
+    fn write(&mut self, at: usize, state: &mut State, mode: ParameterMode, parameter: &Values, value: Values) {
+        // Immediate mode writes go where the parameter says, the same as position mode
+        let mode = if mode == ParameterMode::Immediate { ParameterMode::Position } else { mode };
+        let addresses = match self.address(state, mode, parameter) {
+            Some(Values::Set(addresses)) => addresses,
+            _ => {
+                self.analysis.complete = false;
+                return;
+            }
+        };
+
+        // Only one possible target definitely changes, otherwise each might keep its old value
+        let certain = addresses.len() == 1;
+        for address in addresses {
+            if address < 0 {
+                self.analysis.faults.insert(at);
+                continue;
+            }
+
+            let address = address as usize;
+            let new = if certain { value.clone() } else { state.cell(self.program, address).union(&value) };
+            state.cells.insert(address, new);
+            let written = match self.analysis.written.get(&address) {
+                None => value.clone(),
+                Some(written) => written.union(&value)
+            };
+            self.analysis.written.insert(address, written);
+        }
+    }
+
+    // Follows the instruction at the address, if the cell there holds this opcode value
+    fn step(&mut self, address: usize, value: i32, state: &State) {
+        if value == 99 {
+            return;
+        }
+
+        let instruction = match self.computer.instructions.get(&Instruction::decode_opcode(value)) {
+            Some(instruction) if value > 0 => instruction,
+            _ => {
+                self.analysis.faults.insert(address);
+                return;
+            }
+        };
+
+        let mut modes = Vec::new();
+        let mut parameters = Vec::new();
+        for i in 0..instruction.parameter_count {
+            match Instruction::decode_parameter_mode(value, i, self.computer.profile) {
+                Err(_) => {
+                    self.analysis.faults.insert(address);
+                    return;
+                },
+                Ok(mode) => modes.push(mode)
+            };
+            parameters.push(state.cell(self.program, address + 1 + i as usize));
+        }
+
+        let mut next = state.clone();
+        let fallthrough = address + instruction.parameter_count as usize + 1;
+        let operation: Option<fn(i32, i32) -> Option<i32>> = match instruction.opcode {
+            1 => Some(i32::checked_add),
+            2 => Some(i32::checked_mul),
+            7 => Some(|a, b| Some((a < b) as i32)),
+            8 => Some(|a, b| Some((a == b) as i32)),
+            _ => None
+        };
+
+        match instruction.opcode {
+            1 | 2 | 7 | 8 => {
+                let a = self.read(address, state, modes[0], &parameters[0]);
+                let b = self.read(address, state, modes[1], &parameters[1]);
+                let result = a.combine(&b, operation.unwrap());
+                self.write(address, &mut next, modes[2], &parameters[2], result);
+            },
+            3 => {
+                let input = self.input.clone();
+                self.write(address, &mut next, modes[0], &parameters[0], input);
+            },
+            4 => {
+                self.read(address, state, modes[0], &parameters[0]);
+            },
+            5 | 6 => {
+                let condition = self.read(address, state, modes[0], &parameters[0]);
+                let targets = self.read(address, state, modes[1], &parameters[1]);
+                let jumps_on_zero = instruction.opcode == 6;
+                let fires = condition.may_be(|value| (value == 0) == jumps_on_zero);
+                let falls_through = condition.may_be(|value| (value == 0) != jumps_on_zero);
+
+                let fired = self.fired.entry(address).or_insert((false, false));
+                fired.0 |= fires;
+                fired.1 |= falls_through;
+
+                if fires {
+                    match targets {
+                        Values::Any => self.analysis.complete = false,
+                        Values::Set(targets) => for target in targets {
+                            if target < 0 {
+                                self.analysis.faults.insert(address);
+                            } else if target as usize == address {
+                                // Jumping to itself leaves the pc where it was, so the computer moves on
+                                self.flow(fallthrough, &next);
+                            } else {
+                                self.flow(target as usize, &next);
+                            }
+                        }
+                    };
+                }
+                if falls_through {
+                    self.flow(fallthrough, &next);
+                }
+                return;
+            },
+            9 => {
+                let offset = self.read(address, state, modes[0], &parameters[0]);
+                next.relative_base = state.relative_base.combine(&offset, i32::checked_add);
+            },
+            HOST_CALL => self.write(address, &mut next, modes[2], &parameters[2], Values::Any),
+            // Nothing is known about what any other instruction does
+            _ => {
+                self.analysis.complete = false;
+                return;
+            }
+        };
+
+        self.flow(fallthrough, &next);
+    }
+}
+
+// Follows every path through the program at once, keeping track of the set of values each cell could
+// hold at each instruction. Input instructions read one of the given inputs, or anything if there
+// are none.
+pub fn analyse(computer: &IntcodeComputer, program: &Program, input: Option<&[i32]>) -> Analysis {
+    let input = match input {
+        None => Values::Any,
+        Some(input) => Values::from_set(input.iter().cloned().collect())
+    };
+
+    let mut analyser = Analyser {
+        computer,
+        program,
+        input,
+        states: BTreeMap::new(),
+        worklist: Vec::new(),
+        fired: BTreeMap::new(),
+        analysis: Analysis {complete: true, reachable: BTreeSet::new(), written: BTreeMap::new(), jumps: BTreeMap::new(), faults: BTreeSet::new()}
+    };
+    analyser.flow(0, &State {cells: BTreeMap::new(), relative_base: Values::one(0)});
+
+    while let Some(address) = analyser.worklist.pop() {
+        if !analyser.analysis.complete {
+            break;
+        }
+
+        let state = analyser.states[&address].clone();
+        analyser.analysis.reachable.insert(address);
+        match state.cell(program, address) {
+            Values::Any => analyser.analysis.complete = false,
+            Values::Set(values) => for value in values {
+                analyser.step(address, value, &state);
+            }
+        };
+    }
+
+    analyser.analysis.jumps = analyser.fired.iter()
+                                      .map(|(&address, &fired)| (address, match fired {
+                                          (true, false) => Firing::Always,
+                                          (false, true) => Firing::Never,
+                                          _ => Firing::Sometimes
+                                      }))
+                                      .collect();
+    return analyser.analysis;
+}
@@ -0,0 +1,82 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+use super::{ExecutionState, IntcodeComputer, Memory, Program};
+
+// A running ASCII program as a stream: reading gives its output as text, and writing gives it
+// input. Outputs that aren't ASCII, like the big number text adventures end with, are written out
+// as decimal numbers. Reading when the program is waiting for input that hasn't been written yet
+// fails with WouldBlock, and reading after it halts gives end of file.
+pub struct AsciiStream<'a> {
+    computer: &'a IntcodeComputer,
+    memory: Memory,
+    input: VecDeque<i32>,
+    output: VecDeque<u8>,
+    halted: bool
+}
+
+impl<'a> AsciiStream<'a> {
+    pub fn new(computer: &'a IntcodeComputer, program: &Program) -> AsciiStream<'a> {
+        return AsciiStream {computer, memory: Memory::initialize(program), input: VecDeque::new(), output: VecDeque::new(), halted: false};
+    }
+
+    // Runs until the program halts or wants input it hasn't been given
+    fn run(&mut self) -> io::Result<()> {
+        let mut input = self.input.iter();
+        let mut output = Vec::new();
+        let state = self.computer.resume(&mut self.memory, &mut input, &mut output);
+        let consumed = self.input.len() - input.len();
+        self.input.drain(..consumed);
+
+        for value in &output {
+            match *value {
+                0..=127 => self.output.push_back(*value as u8),
+                value => self.output.extend(value.to_string().bytes())
+            };
+        }
+
+        return match state {
+            Err(why) => Err(io::Error::other(why.to_string())),
+            Ok(ExecutionState::Halted) => {
+                self.halted = true;
+                Ok(())
+            },
+            Ok(ExecutionState::AwaitingInput) if output.is_empty() =>
+                Err(io::Error::new(io::ErrorKind::WouldBlock, "The program is waiting for input")),
+            Ok(_) => Ok(())
+        };
+    }
+}
+
+impl<'a> Read for AsciiStream<'a> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.output.is_empty() {
+            if self.halted {
+                return Ok(0);
+            }
+            self.run()?;
+        }
+
+        let count = buffer.len().min(self.output.len());
+        for (byte, value) in buffer.iter_mut().zip(self.output.drain(..count)) {
+            *byte = value;
+        }
+        return Ok(count);
+    }
+}
+
+impl<'a> Write for AsciiStream<'a> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        if !buffer.is_ascii() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "ASCII programs only take ASCII input"));
+        }
+
+        self.input.extend(buffer.iter().map(|&byte| byte as i32));
+        return Ok(buffer.len());
+    }
+
+    // Input is only used once the program is read from again
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
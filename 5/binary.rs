@@ -0,0 +1,302 @@
+use std::fmt;
+
+use super::{Profile, Program};
+
+// Layout, all integers being LEB128 varints unless noted:
+//
+//     magic         4 bytes, "ICBF"
+//     version       1 byte
+//     kind          1 byte, 0 for a program and 1 for a snapshot of a running one
+//     word size     1 byte, how many bytes a cell holds when running (only 4 is supported)
+//     profile       1 byte, the instruction set the program was written for
+//     flags         1 byte, bit 0 if there is a symbol table, bit 1 if there is an input tape
+//     pc            only in snapshots
+//     relative base only in snapshots, zigzag encoded
+//     cell count, then each cell zigzag encoded
+//     symbol count, then each symbol's address, name length and UTF-8 name    (if flagged)
+//     input count, then each input zigzag encoded                             (if flagged)
+pub const MAGIC: &[u8; 4] = b"ICBF";
+pub const VERSION: u8 = 2;
+pub const WORD_SIZE: u8 = 4;
+
+const HAS_SYMBOLS: u8 = 1;
+const HAS_INPUT: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Program,
+    Snapshot
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BinaryFormatError {
+    NotBinary,
+    UnsupportedVersion(u8),
+    UnknownKind(u8),
+    UnsupportedWordSize(u8),
+    UnknownProfile(u8),
+    Truncated,
+    // A varint that doesn't fit in the type it is read into
+    Overflow(usize),
+    InvalidSymbol(usize),
+    TrailingBytes(usize)
+}
+
+impl fmt::Display for BinaryFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BinaryFormatError::NotBinary => write!(f, "Not a binary Intcode file!"),
+            BinaryFormatError::UnsupportedVersion(version) => write!(f, "Unsupported binary format version: {}!", version),
+            BinaryFormatError::UnknownKind(kind) => write!(f, "Unknown binary file kind: {}!", kind),
+            BinaryFormatError::UnsupportedWordSize(size) => write!(f, "Unsupported word size: {} bytes!", size),
+            BinaryFormatError::UnknownProfile(profile) => write!(f, "Unknown instruction set profile: {}!", profile),
+            BinaryFormatError::Truncated => write!(f, "The file ends part way through!"),
+            BinaryFormatError::Overflow(offset) => write!(f, "Value at byte {} is too big!", offset),
+            BinaryFormatError::InvalidSymbol(offset) => write!(f, "Symbol name at byte {} isn't valid UTF-8!", offset),
+            BinaryFormatError::TrailingBytes(offset) => write!(f, "Unexpected data after byte {}!", offset)
+        }
+    }
+}
+
+// A program, or a snapshot of one part way through running, plus whatever else is needed to run it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub kind: Kind,
+    pub profile: Profile,
+    pub program_counter: usize,
+    pub relative_base: i32,
+    pub cells: Program,
+    pub symbols: Vec<(usize, String)>,
+    pub input: Option<Vec<i32>>
+}
+
+impl Image {
+    pub fn program(profile: Profile, cells: Program) -> Image {
+        return Image {kind: Kind::Program, profile, program_counter: 0, relative_base: 0, cells, symbols: Vec::new(), input: None};
+    }
+
+    pub fn snapshot(profile: Profile, program_counter: usize, relative_base: i32, cells: Program) -> Image {
+        return Image {kind: Kind::Snapshot, profile, program_counter, relative_base, cells, symbols: Vec::new(), input: None};
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if !self.symbols.is_empty() {
+            flags |= HAS_SYMBOLS;
+        }
+        if self.input.is_some() {
+            flags |= HAS_INPUT;
+        }
+
+        let kind = match self.kind {
+            Kind::Program => 0,
+            Kind::Snapshot => 1
+        };
+
+        let profile = match self.profile {
+            Profile::Basic => 0,
+            Profile::Diagnostic => 1,
+            Profile::Extended => 2,
+            Profile::Hosted => 3
+        };
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[VERSION, kind, WORD_SIZE, profile, flags]);
+        if self.kind == Kind::Snapshot {
+            write_varint(&mut bytes, self.program_counter as u64);
+            write_varint(&mut bytes, zigzag(self.relative_base));
+        }
+
+        write_values(&mut bytes, &self.cells);
+
+        if !self.symbols.is_empty() {
+            write_varint(&mut bytes, self.symbols.len() as u64);
+            for (address, name) in &self.symbols {
+                write_varint(&mut bytes, *address as u64);
+                write_varint(&mut bytes, name.len() as u64);
+                bytes.extend_from_slice(name.as_bytes());
+            }
+        }
+
+        if let Some(input) = &self.input {
+            write_values(&mut bytes, input);
+        }
+
+        return bytes;
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Image, BinaryFormatError> {
+        if !is_binary(bytes) {
+            return Err(BinaryFormatError::NotBinary);
+        }
+
+        let mut reader = Reader {bytes, offset: MAGIC.len()};
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(BinaryFormatError::UnsupportedVersion(version));
+        }
+
+        let kind = match reader.byte()? {
+            0 => Kind::Program,
+            1 => Kind::Snapshot,
+            kind => return Err(BinaryFormatError::UnknownKind(kind))
+        };
+
+        let word_size = reader.byte()?;
+        if word_size != WORD_SIZE {
+            return Err(BinaryFormatError::UnsupportedWordSize(word_size));
+        }
+
+        let profile = match reader.byte()? {
+            0 => Profile::Basic,
+            1 => Profile::Diagnostic,
+            2 => Profile::Extended,
+            3 => Profile::Hosted,
+            profile => return Err(BinaryFormatError::UnknownProfile(profile))
+        };
+        let flags = reader.byte()?;
+        let (program_counter, relative_base) = match kind {
+            Kind::Program => (0, 0),
+            Kind::Snapshot => (reader.address()?, reader.value()?)
+        };
+        let cells = reader.values()?;
+
+        let mut symbols = Vec::new();
+        if flags & HAS_SYMBOLS != 0 {
+            for _ in 0..reader.length()? {
+                let address = reader.address()?;
+                let length = reader.length()?;
+                let start = reader.offset;
+                let name = match String::from_utf8(reader.take(length)?.to_vec()) {
+                    Err(_) => return Err(BinaryFormatError::InvalidSymbol(start)),
+                    Ok(name) => name
+                };
+                symbols.push((address, name));
+            }
+        }
+
+        let input = if flags & HAS_INPUT != 0 { Some(reader.values()?) } else { None };
+
+        if reader.offset != bytes.len() {
+            return Err(BinaryFormatError::TrailingBytes(reader.offset));
+        }
+
+        return Ok(Image {kind, profile, program_counter, relative_base, cells, symbols, input});
+    }
+}
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    return bytes.starts_with(MAGIC);
+}
+
+// Symbol files have one "<address> <name>" per line
+pub fn parse_symbols(text: &str) -> Vec<(usize, String)> {
+    return text.lines()
+               .map(|line| line.trim())
+               .filter(|line| !line.is_empty())
+               .map(|line| {
+                   let fields: Vec<&str> = line.splitn(2, char::is_whitespace).collect();
+                   match (fields[0].parse(), fields.get(1)) {
+                       (Ok(address), Some(name)) => (address, name.trim().to_string()),
+                       _ => panic!("Couldn't parse symbol \"{}\"", line)
+                   }
+               })
+               .collect();
+}
+
+fn zigzag(value: i32) -> u64 {
+    return ((value << 1) ^ (value >> 31)) as u32 as u64;
+}
+
+fn unzigzag(value: u32) -> i32 {
+    return (value >> 1) as i32 ^ -((value & 1) as i32);
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_values(bytes: &mut Vec<u8>, values: &[i32]) {
+    write_varint(bytes, values.len() as u64);
+    for &value in values {
+        write_varint(bytes, zigzag(value));
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, BinaryFormatError> {
+        let byte = *self.bytes.get(self.offset).ok_or(BinaryFormatError::Truncated)?;
+        self.offset += 1;
+        return Ok(byte);
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], BinaryFormatError> {
+        let end = self.offset.checked_add(length).ok_or(BinaryFormatError::Truncated)?;
+        let taken = self.bytes.get(self.offset..end).ok_or(BinaryFormatError::Truncated)?;
+        self.offset = end;
+        return Ok(taken);
+    }
+
+    fn varint(&mut self) -> Result<u64, BinaryFormatError> {
+        let start = self.offset;
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 || (shift == 63 && byte > 1) {
+                return Err(BinaryFormatError::Overflow(start));
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    // Addresses can be anywhere in memory, which grows past the end of the program
+    fn address(&mut self) -> Result<usize, BinaryFormatError> {
+        let start = self.offset;
+        let value = self.varint()?;
+        if value > usize::MAX as u64 {
+            return Err(BinaryFormatError::Overflow(start));
+        }
+        return Ok(value as usize);
+    }
+
+    fn length(&mut self) -> Result<usize, BinaryFormatError> {
+        let value = self.varint()?;
+        if value > self.bytes.len() as u64 {
+            // Every cell, symbol or byte of a name takes at least a byte, so the rest can't all be there
+            return Err(BinaryFormatError::Truncated);
+        }
+        return Ok(value as usize);
+    }
+
+    fn value(&mut self) -> Result<i32, BinaryFormatError> {
+        let start = self.offset;
+        let value = self.varint()?;
+        if value > u32::MAX as u64 {
+            return Err(BinaryFormatError::Overflow(start));
+        }
+        return Ok(unzigzag(value as u32));
+    }
+
+    fn values(&mut self) -> Result<Vec<i32>, BinaryFormatError> {
+        let count = self.length()?;
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(self.value()?);
+        }
+        return Ok(values);
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::linker::{ObjectModule, Relocation, RelocationKind};
+
+// A language small enough to compile straight to Intcode:
+//
+//     n = input();
+//     total = 0;
+//     while (0 < n) {
+//         if (n == 3) { output(-1); } else { total = total + n * n; }
+//         n = n - 1;
+//     }
+//     output(total);
+//     export total;
+//
+// Every value is an integer, conditions are true when they aren't 0, and a variable has to be
+// assigned somewhere earlier in the source than where it is read. Variables belong to the module
+// unless a top level export makes them visible to other modules and symbol files.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+    UnexpectedCharacter { line: usize, character: char },
+    UnexpectedToken { line: usize, found: String, expected: &'static str },
+    NumberTooLarge { line: usize, text: String },
+    UnknownVariable { line: usize, name: String },
+    UnknownExport { line: usize, name: String }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::UnexpectedCharacter { line, character } => write!(f, "Line {}: unexpected character '{}'!", line, character),
+            CompileError::UnexpectedToken { line, found, expected } => write!(f, "Line {}: expected {}, found {}!", line, expected, found),
+            CompileError::NumberTooLarge { line, text } => write!(f, "Line {}: {} doesn't fit in a cell!", line, text),
+            CompileError::UnknownVariable { line, name } => write!(f, "Line {}: {} is read before it is assigned!", line, name),
+            CompileError::UnknownExport { line, name } => write!(f, "Line {}: {} is exported but never assigned!", line, name)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i32),
+    Name(String),
+    Symbol(&'static str),
+    End
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Name(name) => write!(f, "\"{}\"", name),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Token::End => write!(f, "the end of the source")
+        }
+    }
+}
+
+// Longest first, so == isn't read as two =
+const SYMBOLS: [&str; 11] = ["==", "=", "+", "-", "*", "<", "(", ")", "{", "}", ";"];
+
+fn tokenise(source: &str) -> Result<Vec<(Token, usize)>, CompileError> {
+    let mut tokens = Vec::new();
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        // Anything after // is a comment
+        let mut rest = text.split("//").next().unwrap().trim_start();
+        while let Some(character) = rest.chars().next() {
+            let length = if character.is_ascii_digit() {
+                let length = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                match rest[..length].parse() {
+                    Err(_) => return Err(CompileError::NumberTooLarge {line, text: rest[..length].to_string()}),
+                    Ok(value) => tokens.push((Token::Number(value), line))
+                };
+                length
+            } else if character.is_alphabetic() || character == '_' {
+                let length = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                tokens.push((Token::Name(rest[..length].to_string()), line));
+                length
+            } else {
+                let symbol = match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                    None => return Err(CompileError::UnexpectedCharacter {line, character}),
+                    Some(symbol) => symbol
+                };
+                tokens.push((Token::Symbol(symbol), line));
+                symbol.len()
+            };
+            rest = rest[length..].trim_start();
+        }
+    }
+
+    let last_line = source.lines().count().max(1);
+    tokens.push((Token::End, last_line));
+    return Ok(tokens);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    LessThan,
+    Equals
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expression {
+    Number(i32),
+    Variable { name: String, line: usize },
+    Input,
+    Negate(Box<Expression>),
+    Binary(Box<Expression>, Operator, Box<Expression>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Statement {
+    Assign { name: String, value: Expression },
+    Output(Expression),
+    If { condition: Expression, then: Vec<Statement>, otherwise: Vec<Statement> },
+    While { condition: Expression, body: Vec<Statement> }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    // Names given to export, with the line they were on
+    exports: Vec<(String, usize)>
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        return &self.tokens[self.position].0;
+    }
+
+    fn line(&self) -> usize {
+        return self.tokens[self.position].1;
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        return token;
+    }
+
+    fn unexpected<T>(&self, expected: &'static str) -> Result<T, CompileError> {
+        return Err(CompileError::UnexpectedToken {line: self.line(), found: self.peek().to_string(), expected});
+    }
+
+    fn expect(&mut self, symbol: &'static str, expected: &'static str) -> Result<(), CompileError> {
+        if *self.peek() != Token::Symbol(symbol) {
+            return self.unexpected(expected);
+        }
+        self.next();
+        return Ok(());
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        return *self.peek() == Token::Name(name.to_string());
+    }
+
+    fn program(&mut self) -> Result<Vec<Statement>, CompileError> {
+        let mut statements = Vec::new();
+        while *self.peek() != Token::End {
+            if self.is_name("export") {
+                self.export()?;
+            } else {
+                statements.push(self.statement()?);
+            }
+        }
+        return Ok(statements);
+    }
+
+    fn export(&mut self) -> Result<(), CompileError> {
+        self.next();
+        let line = self.line();
+        match self.next() {
+            Token::Name(name) => self.exports.push((name, line)),
+            _ => {
+                self.position -= 1;
+                return self.unexpected("a variable to export");
+            }
+        };
+        return self.expect(";", "';'");
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, CompileError> {
+        self.expect("{", "'{'")?;
+        let mut statements = Vec::new();
+        while *self.peek() != Token::Symbol("}") {
+            statements.push(self.statement()?);
+        }
+        self.next();
+        return Ok(statements);
+    }
+
+    // The condition of an if or while, in brackets
+    fn condition(&mut self) -> Result<Expression, CompileError> {
+        self.expect("(", "'('")?;
+        let condition = self.expression()?;
+        self.expect(")", "')'")?;
+        return Ok(condition);
+    }
+
+    fn statement(&mut self) -> Result<Statement, CompileError> {
+        if self.is_name("if") {
+            self.next();
+            let condition = self.condition()?;
+            let then = self.block()?;
+            let otherwise = if self.is_name("else") {
+                self.next();
+                self.block()?
+            } else {
+                Vec::new()
+            };
+            return Ok(Statement::If {condition, then, otherwise});
+        }
+
+        if self.is_name("while") {
+            self.next();
+            let condition = self.condition()?;
+            let body = self.block()?;
+            return Ok(Statement::While {condition, body});
+        }
+
+        let statement = if self.is_name("output") {
+            self.next();
+            Statement::Output(self.condition()?)
+        } else {
+            let name = match self.peek() {
+                Token::Name(name) if !["input", "else", "export"].contains(&name.as_str()) => name.clone(),
+                _ => return self.unexpected("a statement")
+            };
+            self.next();
+            self.expect("=", "'='")?;
+            Statement::Assign {name, value: self.expression()?}
+        };
+
+        self.expect(";", "';'")?;
+        return Ok(statement);
+    }
+
+    // Comparisons bind loosest, then addition and subtraction, then multiplication, then negation
+    fn expression(&mut self) -> Result<Expression, CompileError> {
+        let mut expression = self.sum()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Symbol("<") => Operator::LessThan,
+                Token::Symbol("==") => Operator::Equals,
+                _ => return Ok(expression)
+            };
+            self.next();
+            expression = Expression::Binary(Box::new(expression), operator, Box::new(self.sum()?));
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expression, CompileError> {
+        let mut expression = self.product()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Symbol("+") => Operator::Add,
+                Token::Symbol("-") => Operator::Subtract,
+                _ => return Ok(expression)
+            };
+            self.next();
+            expression = Expression::Binary(Box::new(expression), operator, Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression, CompileError> {
+        let mut expression = self.unary()?;
+        while *self.peek() == Token::Symbol("*") {
+            self.next();
+            expression = Expression::Binary(Box::new(expression), Operator::Multiply, Box::new(self.unary()?));
+        }
+        return Ok(expression);
+    }
+
+    fn unary(&mut self) -> Result<Expression, CompileError> {
+        if *self.peek() == Token::Symbol("-") {
+            self.next();
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        return self.primary();
+    }
+
+    fn primary(&mut self) -> Result<Expression, CompileError> {
+        let line = self.line();
+        return match self.next() {
+            Token::Number(value) => Ok(Expression::Number(value)),
+            Token::Symbol("(") => {
+                let expression = self.expression()?;
+                self.expect(")", "')'")?;
+                Ok(expression)
+            },
+            Token::Name(ref name) if name == "input" => {
+                self.expect("(", "'('")?;
+                self.expect(")", "')'")?;
+                Ok(Expression::Input)
+            },
+            Token::Name(name) => Ok(Expression::Variable {name, line}),
+            _ => {
+                self.position -= 1;
+                self.unexpected("an expression")
+            }
+        };
+    }
+}
+
+// A cell whose address isn't known until all the code has been generated
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Slot {
+    Variable(usize),
+    Temporary(usize),
+    // An address in the code, which only jumps use
+    Label(usize)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value {
+    Constant(i32),
+    // A position mode operand
+    Cell(Slot),
+    // An immediate operand holding the address of a label
+    Address(Slot)
+}
+
+struct Generator {
+    code: Vec<i32>,
+    // Cells that will hold the address of a slot
+    fixups: Vec<(usize, Slot)>,
+    labels: Vec<usize>,
+    variables: BTreeMap<String, usize>,
+    // Variables that have been assigned so far, which are the only ones that can be read
+    assigned: BTreeSet<String>,
+    // Temporaries are used like a stack, since an expression's operands are finished with by the time it is worked out
+    temporaries: usize,
+    most_temporaries: usize
+}
+
+impl Generator {
+    fn emit(&mut self, opcode: i32, operands: &[Value]) {
+        let mut instruction = opcode;
+        let start = self.code.len();
+        self.code.push(0);
+        for (index, operand) in operands.iter().enumerate() {
+            match *operand {
+                Value::Constant(value) => {
+                    instruction += 10i32.pow(index as u32 + 2);
+                    self.code.push(value);
+                },
+                Value::Cell(slot) => {
+                    self.fixups.push((self.code.len(), slot));
+                    self.code.push(0);
+                },
+                Value::Address(slot) => {
+                    instruction += 10i32.pow(index as u32 + 2);
+                    self.fixups.push((self.code.len(), slot));
+                    self.code.push(0);
+                }
+            };
+        }
+        self.code[start] = instruction;
+    }
+
+    fn new_label(&mut self) -> Slot {
+        self.labels.push(0);
+        return Slot::Label(self.labels.len() - 1);
+    }
+
+    fn place(&mut self, label: Slot) {
+        if let Slot::Label(index) = label {
+            self.labels[index] = self.code.len();
+        }
+    }
+
+    fn temporary(&mut self) -> Slot {
+        self.temporaries += 1;
+        self.most_temporaries = self.most_temporaries.max(self.temporaries);
+        return Slot::Temporary(self.temporaries - 1);
+    }
+
+    // Frees the temporaries holding values that have been used up
+    fn release(&mut self, values: &[Value]) {
+        for value in values {
+            if let Value::Cell(Slot::Temporary(index)) = *value {
+                self.temporaries = self.temporaries.min(index);
+            }
+        }
+    }
+
+    // Works out a op b into the destination, or a temporary if there isn't one
+    fn combine(&mut self, opcode: i32, a: Value, b: Value, destination: Option<Slot>) -> Value {
+        self.release(&[a, b]);
+        let destination = destination.unwrap_or_else(|| self.temporary());
+        self.emit(opcode, &[a, b, Value::Cell(destination)]);
+        return Value::Cell(destination);
+    }
+
+    fn negate(&mut self, value: Value, destination: Option<Slot>) -> Value {
+        return match value {
+            Value::Constant(value) if value != i32::MIN => Value::Constant(-value),
+            // There's no subtraction, so this multiplies by -1
+            _ => self.combine(2, value, Value::Constant(-1), destination)
+        };
+    }
+
+    fn expression(&mut self, expression: &Expression, destination: Option<Slot>) -> Result<Value, CompileError> {
+        return match expression {
+            Expression::Number(value) => Ok(Value::Constant(*value)),
+            Expression::Variable { name, line } => match self.variables.get(name) {
+                Some(&index) if self.assigned.contains(name) => Ok(Value::Cell(Slot::Variable(index))),
+                _ => Err(CompileError::UnknownVariable {line: *line, name: name.clone()})
+            },
+            Expression::Input => {
+                let destination = destination.unwrap_or_else(|| self.temporary());
+                self.emit(3, &[Value::Cell(destination)]);
+                Ok(Value::Cell(destination))
+            },
+            Expression::Negate(operand) => {
+                let value = self.expression(operand, None)?;
+                Ok(self.negate(value, destination))
+            },
+            Expression::Binary(left, operator, right) => {
+                let a = self.expression(left, None)?;
+                let b = self.expression(right, None)?;
+
+                if let (Value::Constant(x), Value::Constant(y)) = (a, b) {
+                    // Anything that would overflow is left for the computer to fail on
+                    let folded = match operator {
+                        Operator::Add => x.checked_add(y),
+                        Operator::Subtract => x.checked_sub(y),
+                        Operator::Multiply => x.checked_mul(y),
+                        Operator::LessThan => Some((x < y) as i32),
+                        Operator::Equals => Some((x == y) as i32)
+                    };
+                    if let Some(value) = folded {
+                        return Ok(Value::Constant(value));
+                    }
+                }
+
+                Ok(match operator {
+                    Operator::Add => self.combine(1, a, b, destination),
+                    Operator::Subtract => {
+                        let negated = self.negate(b, None);
+                        self.combine(1, a, negated, destination)
+                    },
+                    Operator::Multiply => self.combine(2, a, b, destination),
+                    Operator::LessThan => self.combine(7, a, b, destination),
+                    Operator::Equals => self.combine(8, a, b, destination)
+                })
+            }
+        };
+    }
+
+    // Jumps to the label if the condition is false
+    fn branch_unless(&mut self, condition: &Expression, label: Slot) -> Result<(), CompileError> {
+        let condition = self.expression(condition, None)?;
+        self.release(&[condition]);
+        self.emit(6, &[condition, Value::Address(label)]);
+        return Ok(());
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        for statement in statements {
+            match statement {
+                Statement::Assign { name, value } => {
+                    let count = self.variables.len();
+                    let variable = Slot::Variable(*self.variables.entry(name.clone()).or_insert(count));
+                    let value = self.expression(value, Some(variable))?;
+                    if value != Value::Cell(variable) {
+                        self.emit(1, &[value, Value::Constant(0), Value::Cell(variable)]);
+                    }
+                    self.release(&[value]);
+                    self.assigned.insert(name.clone());
+                },
+                Statement::Output(value) => {
+                    let value = self.expression(value, None)?;
+                    self.release(&[value]);
+                    self.emit(4, &[value]);
+                },
+                Statement::If { condition, then, otherwise } => {
+                    let otherwise_label = self.new_label();
+                    let end = self.new_label();
+                    self.branch_unless(condition, otherwise_label)?;
+                    self.statements(then)?;
+                    if !otherwise.is_empty() {
+                        self.emit(5, &[Value::Constant(1), Value::Address(end)]);
+                    }
+                    self.place(otherwise_label);
+                    self.statements(otherwise)?;
+                    self.place(end);
+                },
+                Statement::While { condition, body } => {
+                    let start = self.new_label();
+                    let end = self.new_label();
+                    self.place(start);
+                    self.branch_unless(condition, end)?;
+                    self.statements(body)?;
+                    self.emit(5, &[Value::Constant(1), Value::Address(start)]);
+                    self.place(end);
+                }
+            };
+        }
+        return Ok(());
+    }
+}
+
+// Compiles the source into a module with every address relocatable, so it can be linked with others
+// or on its own. The code only uses opcodes 1 to 8 in position and immediate mode, so it runs with
+// the diagnostic profile. Variables, then temporaries, go in cells after the code. Only the variables
+// the source exports get symbols, so modules can use the same names without clashing.
+pub fn compile(source: &str, name: &str) -> Result<ObjectModule, CompileError> {
+    let mut parser = Parser {tokens: tokenise(source)?, position: 0, exports: Vec::new()};
+    let statements = parser.program()?;
+
+    let mut generator = Generator {
+        code: Vec::new(),
+        fixups: Vec::new(),
+        labels: Vec::new(),
+        variables: BTreeMap::new(),
+        assigned: BTreeSet::new(),
+        temporaries: 0,
+        most_temporaries: 0
+    };
+    generator.statements(&statements)?;
+    generator.emit(99, &[]);
+
+    let variables_start = generator.code.len();
+    let temporaries_start = variables_start + generator.variables.len();
+    let mut module = ObjectModule::new(name);
+    module.code = generator.code.clone();
+    module.code.resize(temporaries_start + generator.most_temporaries, 0);
+
+    for &(offset, slot) in &generator.fixups {
+        let (address, kind) = match slot {
+            Slot::Variable(index) => (variables_start + index, RelocationKind::Position),
+            Slot::Temporary(index) => (temporaries_start + index, RelocationKind::Position),
+            Slot::Label(index) => (generator.labels[index], RelocationKind::Jump)
+        };
+        module.code[offset] = address as i32;
+        module.relocations.push(Relocation {offset, kind, symbol: None});
+    }
+    for (variable, line) in parser.exports {
+        match generator.variables.get(&variable) {
+            None => return Err(CompileError::UnknownExport {line, name: variable}),
+            Some(index) => module.exports.insert(variable, variables_start + index)
+        };
+    }
+
+    return Ok(module);
+}
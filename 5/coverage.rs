@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::disassembly;
+use super::intcode::{Instruction, Observer, Verdict};
+use super::{IntcodeComputer, Memory, Program};
+
+// What happened to each cell of memory during a run
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    // Address of an instruction -> how many times it ran
+    pub executed: BTreeMap<usize, usize>,
+    // Address of an instruction -> how many cells it took up when it ran
+    pub instruction_sizes: BTreeMap<usize, usize>,
+    // Cells read as an instruction's parameters
+    pub operand_reads: BTreeSet<usize>,
+    // Cells read through position or relative mode parameters
+    pub data_reads: BTreeSet<usize>,
+    pub writes: BTreeSet<usize>
+}
+
+impl Observer for Coverage {
+    fn after_instruction(&mut self, _memory: &Memory, address: usize, instruction: &Instruction) -> Verdict {
+        let size = instruction.parameter_count as usize + 1;
+        *self.executed.entry(address).or_insert(0) += 1;
+        self.instruction_sizes.insert(address, size);
+        self.operand_reads.extend(address + 1..address + size);
+        return Verdict::Continue;
+    }
+
+    fn memory_read(&mut self, _pc: usize, address: usize, _value: i32) -> Verdict {
+        self.data_reads.insert(address);
+        return Verdict::Continue;
+    }
+
+    fn memory_write(&mut self, _pc: usize, address: usize, _old: i32, _new: i32) -> Verdict {
+        self.writes.insert(address);
+        return Verdict::Continue;
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        return Coverage {
+            executed: BTreeMap::new(),
+            instruction_sizes: BTreeMap::new(),
+            operand_reads: BTreeSet::new(),
+            data_reads: BTreeSet::new(),
+            writes: BTreeSet::new()
+        };
+    }
+
+    // Splits the program into instructions and data. Anything that ran is an instruction; anything
+    // else is taken to be one if it decodes and the run never used any of its cells as data.
+    pub fn listing(&self, computer: &IntcodeComputer, program: &Program) -> Vec<ListingLine> {
+        let executed_cells: BTreeSet<usize> = self.instruction_sizes.iter()
+            .flat_map(|(&address, &size)| address..address + size)
+            .collect();
+        let used_as_data = |address: usize| self.data_reads.contains(&address) || self.writes.contains(&address);
+
+        let mut lines = Vec::new();
+        let mut untouched = Vec::new();
+        let mut address = 0;
+        while address < program.len() {
+            let instruction = match disassembly::decode(computer, program, address) {
+                Some(ref instruction) if self.executed.contains_key(&address) => Some(instruction.clone()),
+                Some(ref instruction) if instruction.cells().all(|cell| !used_as_data(cell) && !self.executed.contains_key(&cell)) &&
+                                         (instruction.address + 1..instruction.next_address()).all(|cell| !executed_cells.contains(&cell)) => Some(instruction.clone()),
+                _ => None
+            };
+
+            let (line, size) = match instruction {
+                // The program must have written this instruction before running it
+                None if self.executed.contains_key(&address) => {
+                    let text = format!("{}  (overwritten before running)", program[address]);
+                    (ListingLine {address, executions: Some(self.executed[&address]), text}, self.instruction_sizes[&address])
+                },
+                Some(instruction) => {
+                    let executions = Some(self.executed.get(&address).cloned().unwrap_or(0));
+                    (ListingLine {address, executions, text: instruction.to_string()}, instruction.cells().len())
+                },
+                None if !used_as_data(address) && !self.operand_reads.contains(&address) => {
+                    untouched.push(address);
+                    address += 1;
+                    continue;
+                },
+                None => {
+                    let mut uses = Vec::new();
+                    if self.data_reads.contains(&address) {
+                        uses.push("read");
+                    }
+                    if self.writes.contains(&address) {
+                        uses.push("written");
+                    }
+                    if uses.is_empty() {
+                        uses.push("operand");
+                    }
+                    (ListingLine {address, executions: None, text: format!("{}  ({})", program[address], uses.join(", "))}, 1)
+                }
+            };
+
+            lines.extend(untouched_data(program, &untouched));
+            untouched.clear();
+            address += size;
+            lines.push(line);
+        }
+
+        lines.extend(untouched_data(program, &untouched));
+        return lines;
+    }
+
+    // gcov style: execution counts down the side, ##### for instructions that never ran
+    pub fn annotated_listing(&self, computer: &IntcodeComputer, program: &Program) -> String {
+        let lines = self.listing(computer, program);
+        let instructions = lines.iter().filter(|line| line.executions.is_some()).count();
+        let executed = lines.iter().filter(|line| line.executions.unwrap_or(0) > 0).count();
+
+        let mut listing = String::new();
+        writeln!(listing, "Executed {} of {} instructions ({:.1}%)", executed, instructions, percentage(executed, instructions)).unwrap();
+        for line in lines {
+            let count = match line.executions {
+                None => String::new(),
+                Some(0) => String::from("#####"),
+                Some(count) => count.to_string()
+            };
+            writeln!(listing, "{:>9} | {:>5}: {}", count, line.address, line.text).unwrap();
+        }
+        return listing;
+    }
+
+    // An lcov tracefile for the annotated listing, so line N of the listing is line N of the source file
+    pub fn lcov(&self, computer: &IntcodeComputer, program: &Program, listing_file: &str) -> String {
+        let lines = self.listing(computer, program);
+
+        let mut lcov = String::new();
+        writeln!(lcov, "TN:").unwrap();
+        writeln!(lcov, "SF:{}", listing_file).unwrap();
+        for (index, line) in lines.iter().enumerate() {
+            if let Some(executions) = line.executions {
+                // The listing starts with a summary line, and lcov counts lines from 1
+                writeln!(lcov, "DA:{},{}", index + 2, executions).unwrap();
+            }
+        }
+        writeln!(lcov, "LF:{}", lines.iter().filter(|line| line.executions.is_some()).count()).unwrap();
+        writeln!(lcov, "LH:{}", lines.iter().filter(|line| line.executions.unwrap_or(0) > 0).count()).unwrap();
+        writeln!(lcov, "end_of_record").unwrap();
+        return lcov;
+    }
+}
+
+pub struct ListingLine {
+    pub address: usize,
+    // How many times the instruction here ran, or None if this is data
+    pub executions: Option<usize>,
+    pub text: String
+}
+
+// Cells the run never touched, in runs of consecutive addresses
+fn untouched_data(program: &Program, addresses: &[usize]) -> Vec<ListingLine> {
+    let mut lines = Vec::new();
+    for chunk in addresses.chunks(8) {
+        let values: Vec<String> = chunk.iter().map(|&address| program[address].to_string()).collect();
+        lines.push(ListingLine {address: chunk[0], executions: None, text: format!("data {}", values.join(", "))});
+    }
+    return lines;
+}
+
+fn percentage(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 100.0;
+    }
+    return part as f64 * 100.0 / total as f64;
+}
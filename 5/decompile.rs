@@ -0,0 +1,490 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use super::analysis::{self, Analysis, Firing};
+use super::disassembly::{self, ControlFlow, DecodedInstruction, Operand};
+use super::{IntcodeComputer, Program};
+
+// A run of cells in the program, in address order
+enum Item {
+    Code(DecodedInstruction),
+    // An instruction that doesn't decode as it stands, but gets written to before it runs
+    Patched { address: usize, cells: Vec<i32> },
+    Data { address: usize, value: i32 }
+}
+
+impl Item {
+    fn address(&self) -> usize {
+        return match self {
+            Item::Code(instruction) => instruction.address,
+            Item::Patched { address, .. } => *address,
+            Item::Data { address, .. } => *address
+        };
+    }
+
+    fn next_address(&self) -> usize {
+        return match self {
+            Item::Code(instruction) => instruction.next_address(),
+            Item::Patched { address, cells } => address + cells.len(),
+            Item::Data { address, .. } => address + 1
+        };
+    }
+
+    fn jump(&self) -> Option<&DecodedInstruction> {
+        return match self {
+            Item::Code(instruction) if instruction.is_jump() => Some(instruction),
+            _ => None
+        };
+    }
+}
+
+enum Statement {
+    Simple { address: usize, text: String },
+    If { address: usize, condition: String, then: Vec<Statement>, otherwise: Vec<Statement> },
+    While { address: usize, condition: String, body: Vec<Statement> },
+    DoWhile { address: usize, body: Vec<Statement>, condition: String }
+}
+
+#[derive(Clone, Copy)]
+struct Loop {
+    header: usize,
+    exit: usize
+}
+
+// Decodes the program front to back. Instructions found by following the control flow take
+// priority, and anything between them that decodes is assumed to be code too, since it may
+// only be reachable through jumps we couldn't work out.
+fn sweep(computer: &IntcodeComputer, program: &Program, control_flow: &ControlFlow, written: &BTreeSet<usize>) -> Vec<Item> {
+    let mut parameter_counts: Vec<usize> = computer.instructions.values().map(|instruction| instruction.parameter_count as usize).collect();
+    parameter_counts.sort();
+    parameter_counts.dedup();
+
+    // Something that only decodes by sweeping is more likely data if it overlaps real code, or cells real code writes to
+    let traced_writes = control_flow.write_targets();
+    let plausible = |instruction: &DecodedInstruction| {
+        if control_flow.instructions.contains_key(&instruction.address) {
+            return true;
+        }
+        return instruction.cells().all(|cell| !traced_writes.contains(&cell)) &&
+               (instruction.address + 1..instruction.next_address()).all(|cell| !control_flow.instructions.contains_key(&cell));
+    };
+
+    // Only cells that execution can actually arrive at could be patched instructions, rather than data
+    let reachable = |items: &Vec<Item>, address: usize| {
+        return control_flow.jump_targets.contains_key(&address) || match items.last() {
+            Some(Item::Code(previous)) => !previous.is_halt() && previous.constant_condition() != Some(true),
+            Some(Item::Patched { .. }) => true,
+            _ => false
+        };
+    };
+
+    let mut items = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        let item = match control_flow.instructions.get(&address).cloned().or_else(|| disassembly::decode(computer, program, address)) {
+            Some(ref instruction) if plausible(instruction) => Item::Code(instruction.clone()),
+            _ if written.contains(&address) && reachable(&items, address) => {
+                // Guess how long the patched instruction is by finding the length after which decoding makes sense again
+                let length = parameter_counts.iter()
+                                             .map(|count| count + 1)
+                                             .find(|length| disassembly::decode(computer, program, address + length).is_some())
+                                             .unwrap_or(1);
+                let end = (address + length).min(program.len());
+                Item::Patched {address, cells: program[address..end].to_vec()}
+            },
+            _ => Item::Data {address, value: program[address]}
+        };
+
+        address = item.next_address();
+        items.push(item);
+    }
+
+    return items;
+}
+
+// Decides what to call each cell that instructions use as data
+struct Names {
+    variables: BTreeMap<usize, String>,
+    written: BTreeSet<usize>,
+    program: Program
+}
+
+impl Names {
+    fn new(items: &[Item], program: &Program) -> Names {
+        let code: BTreeSet<usize> = items.iter()
+                                         .filter(|item| !matches!(item, Item::Data { .. }))
+                                         .flat_map(|item| item.address()..item.next_address())
+                                         .collect();
+
+        let mut inputs = BTreeSet::new();
+        let mut accumulators = BTreeSet::new();
+        let mut written = BTreeSet::new();
+        for item in items {
+            if let Item::Code(instruction) = item {
+                for target in instruction.write_targets() {
+                    written.insert(target);
+                    if instruction.opcode == 3 {
+                        inputs.insert(target);
+                    }
+                    if instruction.data_reads().contains(&target) {
+                        accumulators.insert(target);
+                    }
+                }
+            }
+        }
+
+        let variables = written.iter()
+                               .filter(|cell| !code.contains(cell))
+                               .map(|&cell| {
+                                   let role = if inputs.contains(&cell) { "in" } else if accumulators.contains(&cell) { "acc" } else { "tmp" };
+                                   (cell, format!("{}_{}", role, cell))
+                               })
+                               .collect();
+
+        return Names {variables, written, program: program.clone()};
+    }
+
+    // The value of an operand, if it can never change
+    fn constant(&self, operand: &Operand) -> Option<i32> {
+        return match operand {
+            Operand::Immediate(value) => Some(*value),
+            Operand::Position(cell) if !self.written.contains(cell) => self.program.get(*cell).cloned(),
+            Operand::Position(_) => None
+        };
+    }
+
+    fn operand(&self, operand: &Operand) -> String {
+        return match operand {
+            Operand::Immediate(value) => value.to_string(),
+            Operand::Position(cell) => match (self.variables.get(cell), self.constant(operand)) {
+                (Some(name), _) => name.clone(),
+                // Cells nothing writes to are constants, so just show the value
+                (None, Some(value)) => value.to_string(),
+                (None, None) => format!("mem[{}]", cell)
+            }
+        };
+    }
+}
+
+struct Decompiler {
+    items: Vec<Item>,
+    index_of: HashMap<usize, usize>,
+    // Jump target -> the jumps that land there
+    incoming: BTreeMap<usize, BTreeSet<usize>>,
+    labels: BTreeSet<usize>,
+    names: Names,
+    analysis: Analysis,
+    program_length: usize
+}
+
+impl Decompiler {
+    fn address(&self, index: usize) -> usize {
+        return if index < self.items.len() { self.items[index].address() } else { self.program_length };
+    }
+
+    // The item a jump lands on, if it is the start of one
+    fn target_index(&self, jump: &DecodedInstruction) -> Option<usize> {
+        return match jump.operands[1] {
+            Operand::Immediate(target) if target >= 0 => self.index_of.get(&(target as usize)).cloned(),
+            _ => None
+        };
+    }
+
+    fn condition(&self, jump: &DecodedInstruction, negate: bool) -> String {
+        let jumps_if_non_zero = (jump.opcode == 5) != negate;
+        return match self.names.constant(&jump.operands[0]) {
+            Some(value) => ((value != 0) == jumps_if_non_zero).to_string(),
+            None => format!("{} {} 0", self.names.operand(&jump.operands[0]), if jumps_if_non_zero { "!=" } else { "==" })
+        };
+    }
+
+    // Whether the items in [start, end) can be pulled out as one block: nothing jumps into the middle
+    // of them, and they only jump within themselves, to their end, or to one of the allowed places
+    fn closed(&self, start: usize, end: usize, allowed: &[usize]) -> bool {
+        let (first, last) = (self.address(start), self.address(end));
+
+        let no_jumps_in = self.incoming.range(first + 1..last)
+                                       .all(|(_, sources)| sources.iter().all(|&source| source >= first && source < last));
+        let no_jumps_out = self.items[start..end].iter().filter_map(Item::jump).all(|jump| {
+            return match self.target_index(jump) {
+                None => true,
+                Some(target) => (start..=end).contains(&target) || allowed.contains(&self.address(target))
+            };
+        });
+
+        return no_jumps_in && no_jumps_out;
+    }
+
+    fn structure(&mut self, start: usize, end: usize, context: Option<Loop>) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        let mut index = start;
+        while index < end {
+            let address = self.address(index);
+
+            // A loop is everything from here to the last jump back here
+            let back_jump = (index..end).rev().find(|&candidate| match self.items[candidate].jump() {
+                Some(jump) => self.target_index(jump) == Some(index) && jump.constant_condition() != Some(false),
+                None => false
+            });
+            let is_current_loop = context.map(|context| context.header == address).unwrap_or(false);
+            if let (Some(back_jump), false) = (back_jump, is_current_loop) {
+                let exit = self.address(back_jump + 1);
+                if self.closed(index, back_jump + 1, &[address, exit]) {
+                    let inner = Some(Loop {header: address, exit});
+                    let jump = self.items[back_jump].jump().unwrap().clone();
+
+                    if jump.constant_condition() == Some(true) {
+                        // while (true) { if (x) break; ... } reads better as while (!x) { ... }
+                        let exit_test = self.items[index].jump().cloned()
+                                                         .filter(|test| self.target_index(test).map(|target| self.address(target)) == Some(exit));
+                        let (condition, body) = match exit_test {
+                            Some(test) if test.constant_condition().is_none() => (self.condition(&test, true), self.structure(index + 1, back_jump, inner)),
+                            _ => ("true".to_string(), self.structure(index, back_jump, inner))
+                        };
+                        statements.push(Statement::While {address, condition, body});
+                    } else {
+                        let body = self.structure(index, back_jump, inner);
+                        statements.push(Statement::DoWhile {address, body, condition: self.condition(&jump, false)});
+                    }
+
+                    index = back_jump + 1;
+                    continue;
+                }
+            }
+
+            // A forward conditional jump skips over the body of an if
+            let forward = self.items[index].jump().cloned()
+                                           .filter(|jump| jump.constant_condition().is_none())
+                                           .and_then(|jump| self.target_index(&jump).map(|target| (jump, target)))
+                                           .filter(|&(_, target)| target > index + 1 && target <= end);
+            if let Some((jump, target)) = forward {
+                let loop_targets: Vec<usize> = context.iter().flat_map(|context| vec![context.header, context.exit]).collect();
+
+                // ... and if the body ends by jumping further forward, what it jumps over is the else
+                let otherwise = self.items[target - 1].jump().cloned()
+                                                      .filter(|skip| skip.constant_condition() == Some(true))
+                                                      .and_then(|skip| self.target_index(&skip))
+                                                      .filter(|&skip_target| skip_target > target && skip_target <= end)
+                                                      .filter(|&skip_target| !loop_targets.contains(&self.address(skip_target)))
+                                                      .filter(|&skip_target| self.closed(target, skip_target, &loop_targets));
+
+                // The body is allowed to leave by that jump over the else
+                let mut exits = loop_targets.clone();
+                if let Some(skip_target) = otherwise {
+                    exits.push(self.address(skip_target));
+                }
+
+                if self.closed(index + 1, target, &exits) {
+                    let condition = self.condition(&jump, true);
+                    match otherwise {
+                        Some(skip_target) => {
+                            let then = self.structure(index + 1, target - 1, context);
+                            let otherwise = self.structure(target, skip_target, context);
+                            statements.push(Statement::If {address, condition, then, otherwise});
+                            index = skip_target;
+                        },
+                        None => {
+                            let then = self.structure(index + 1, target, context);
+                            statements.push(Statement::If {address, condition, then, otherwise: Vec::new()});
+                            index = target;
+                        }
+                    };
+                    continue;
+                }
+            }
+
+            // Runs of data are collapsed into one line
+            if let Item::Data { .. } = self.items[index] {
+                let mut values = Vec::new();
+                while index < end {
+                    match self.items[index] {
+                        Item::Data { value, .. } => values.push(value.to_string()),
+                        _ => break
+                    };
+                    index += 1;
+                }
+
+                let text = format!("// data: {}", values.join(", "));
+                statements.push(Statement::Simple {address, text});
+                continue;
+            }
+
+            let text = self.simple(index, context);
+            statements.push(Statement::Simple {address, text});
+            index += 1;
+        }
+
+        return statements;
+    }
+
+    fn simple(&mut self, index: usize, context: Option<Loop>) -> String {
+        let instruction = match &self.items[index] {
+            Item::Code(instruction) => instruction.clone(),
+            Item::Patched { address, cells } => {
+                let cells: Vec<String> = cells.iter().map(|cell| cell.to_string()).collect();
+                return format!("// {}: {} is overwritten before it runs", address, cells.join(", "));
+            },
+            Item::Data { value, .. } => return format!("// data: {}", value)
+        };
+
+        let operands: Vec<String> = instruction.operands.iter().map(|operand| self.names.operand(operand)).collect();
+        let statement = match instruction.opcode {
+            1 | 2 => {
+                let operator = if instruction.opcode == 1 { "+" } else { "*" };
+                let destination = &operands[2];
+                let (a, b) = (&operands[0], &operands[1]);
+
+                // x = x + y becomes x += y, and adding a negative number becomes a subtraction
+                let (operator, a, b) = if a == destination { (format!("{}=", operator), None, b) }
+                                       else if b == destination { (format!("{}=", operator), None, a) }
+                                       else { (operator.to_string(), Some(a), b) };
+                let (operator, b) = match (operator.starts_with('+'), b.strip_prefix('-')) {
+                    (true, Some(positive)) => (operator.replacen('+', "-", 1), positive.to_string()),
+                    _ => (operator, b.clone())
+                };
+
+                // Adding 0 or multiplying by 1 is just a copy
+                let identity = if instruction.opcode == 1 { "0" } else { "1" };
+                match a {
+                    None => format!("{} {} {};", destination, operator, b),
+                    Some(a) if b == identity => format!("{} = {};", destination, a),
+                    Some(a) if a == identity => format!("{} = {};", destination, b),
+                    Some(a) => format!("{} = {} {} {};", destination, a, operator, b)
+                }
+            },
+            3 => format!("{} = input();", operands[0]),
+            4 => format!("output({});", operands[0]),
+            7 => format!("{} = {} < {};", operands[2], operands[0], operands[1]),
+            8 => format!("{} = {} == {};", operands[2], operands[0], operands[1]),
+            99 => "halt();".to_string(),
+            5 | 6 => {
+                let goto = self.goto(&instruction, context);
+                match (instruction.constant_condition(), self.firing(instruction.address)) {
+                    (Some(true), _) => goto,
+                    (None, Some(Firing::Always)) => format!("{} // always: {}", goto, self.condition(&instruction, false)),
+                    (None, Some(Firing::Never)) => format!("// never taken: if ({}) {}", self.condition(&instruction, false), goto),
+                    _ => format!("if ({}) {}", self.condition(&instruction, false), goto)
+                }
+            },
+            _ => format!("// {}", instruction)
+        };
+
+        return statement;
+    }
+
+    // What the analysis says about a jump, if it could follow the whole program
+    fn firing(&self, address: usize) -> Option<Firing> {
+        return if self.analysis.complete { self.analysis.jumps.get(&address).cloned() } else { None };
+    }
+
+    fn unreachable(&self, address: usize) -> bool {
+        return self.analysis.complete && !self.analysis.reachable.contains(&address) && matches!(self.items.get(self.index_of[&address]), Some(Item::Code(_)));
+    }
+
+    fn goto(&mut self, jump: &DecodedInstruction, context: Option<Loop>) -> String {
+        let target = match jump.operands[1] {
+            Operand::Immediate(target) => target,
+            ref operand => return format!("goto *{};", self.names.operand(operand))
+        };
+
+        if let Some(context) = context {
+            if target as usize == context.exit {
+                return "break;".to_string();
+            } else if target as usize == context.header {
+                return "continue;".to_string();
+            }
+        }
+
+        if target < 0 || !self.index_of.contains_key(&(target as usize)) {
+            return format!("crash({});", target);
+        }
+
+        self.labels.insert(target as usize);
+        return format!("goto L_{};", target);
+    }
+
+    fn print(&self, statements: &[Statement], depth: usize, text: &mut String) {
+        let indent = "    ".repeat(depth);
+        for statement in statements {
+            let address = match statement {
+                Statement::Simple { address, .. } | Statement::If { address, .. } |
+                Statement::While { address, .. } | Statement::DoWhile { address, .. } => *address
+            };
+            if self.labels.contains(&address) {
+                writeln!(text, "{}L_{}:", "    ".repeat(depth - 1), address).unwrap();
+            }
+
+            match statement {
+                Statement::Simple { address, text: line } => {
+                    let note = if self.unreachable(*address) { ", unreachable" } else { "" };
+                    writeln!(text, "{}{:<width$} // {}{}", indent, line, address, note, width = 56 - indent.len()).unwrap();
+                },
+                Statement::If { condition, then, otherwise, .. } => {
+                    writeln!(text, "{}if ({}) {{", indent, condition).unwrap();
+                    self.print(then, depth + 1, text);
+                    if !otherwise.is_empty() {
+                        writeln!(text, "{}}} else {{", indent).unwrap();
+                        self.print(otherwise, depth + 1, text);
+                    }
+                    writeln!(text, "{}}}", indent).unwrap();
+                },
+                Statement::While { condition, body, .. } => {
+                    writeln!(text, "{}while ({}) {{", indent, condition).unwrap();
+                    self.print(body, depth + 1, text);
+                    writeln!(text, "{}}}", indent).unwrap();
+                },
+                Statement::DoWhile { body, condition, .. } => {
+                    writeln!(text, "{}do {{", indent).unwrap();
+                    self.print(body, depth + 1, text);
+                    writeln!(text, "{}}} while ({});", indent, condition).unwrap();
+                }
+            };
+        }
+    }
+}
+
+// Recovers if/while structure from the program's jumps and prints it as C-like pseudocode. Cells
+// that instructions write to become named variables (in_ for input, acc_ for cells updated in
+// place, tmp_ for anything else), and cells that are never written are shown as their value. Jumps
+// the analysis shows always or never fire, and code it shows can't run, are marked, assuming input
+// instructions only ever read one of the inputs given.
+pub fn decompile(computer: &IntcodeComputer, program: &Program, input: Option<&[i32]>) -> String {
+    let control_flow = disassembly::trace_control_flow(computer, program);
+
+    // Patched instructions can only be spotted once we know everything that gets written
+    let unpatched = sweep(computer, program, &control_flow, &BTreeSet::new());
+    let written: BTreeSet<usize> = unpatched.iter()
+                                            .filter_map(|item| match item { Item::Code(instruction) => Some(instruction.write_targets()), _ => None })
+                                            .flatten()
+                                            .collect();
+    let items = sweep(computer, program, &control_flow, &written);
+
+    let mut incoming = BTreeMap::new();
+    for item in &items {
+        if let Some(Operand::Immediate(target)) = item.jump().and_then(|jump| jump.jump_target()) {
+            if target >= 0 {
+                incoming.entry(target as usize).or_insert_with(BTreeSet::new).insert(item.address());
+            }
+        }
+    }
+
+    let names = Names::new(&items, program);
+    let index_of = items.iter().enumerate().map(|(index, item)| (item.address(), index)).collect();
+    let analysis = analysis::analyse(computer, program, input);
+    let mut decompiler = Decompiler {items, index_of, incoming, labels: BTreeSet::new(), names, analysis, program_length: program.len()};
+
+    let item_count = decompiler.items.len();
+    let statements = decompiler.structure(0, item_count, None);
+
+    let mut text = String::new();
+    writeln!(text, "// Decompiled from a {} cell Intcode program", program.len()).unwrap();
+    for (cell, name) in &decompiler.names.variables {
+        writeln!(text, "int {} = {};", name, program.get(*cell).unwrap_or(&0)).unwrap();
+    }
+    writeln!(text).unwrap();
+    writeln!(text, "void main() {{").unwrap();
+    decompiler.print(&statements, 1, &mut text);
+    writeln!(text, "}}").unwrap();
+
+    return text;
+}
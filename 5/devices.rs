@@ -0,0 +1,100 @@
+use std::time::Instant;
+
+use super::intcode::Device;
+use super::screen::Screen;
+
+// One cell holding the milliseconds since the clock was started. Writing anything restarts it.
+pub struct Clock {
+    started: Instant
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        return Clock {started: Instant::now()};
+    }
+}
+
+impl Device for Clock {
+    fn read(&mut self, _offset: usize) -> i32 {
+        return (self.started.elapsed().as_millis() % (i32::MAX as u128 + 1)) as i32;
+    }
+
+    fn write(&mut self, _offset: usize, _value: i32) {
+        self.started = Instant::now();
+    }
+}
+
+// One cell giving a new non-negative pseudo random number each time it is read. Writing to it
+// sets the seed, so runs can be repeated.
+pub struct Random {
+    state: u32
+}
+
+impl Random {
+    pub fn new(seed: i32) -> Random {
+        let mut random = Random {state: 0};
+        random.write(0, seed);
+        return random;
+    }
+}
+
+impl Device for Random {
+    // xorshift32
+    fn read(&mut self, _offset: usize) -> i32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        return (self.state >> 1) as i32;
+    }
+
+    fn write(&mut self, _offset: usize, value: i32) {
+        // xorshift gets stuck on zero
+        self.state = if value == 0 { 1 } else { value as u32 };
+    }
+}
+
+// width * height cells, one per tile, in rows from the top left. Each cell holds a tile id, drawn
+// the same way as tiles sent to a screen through output.
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    screen: Screen
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        // The score never goes in the framebuffer, so put it somewhere no tile can be
+        let mut screen = Screen::new((-1, -1));
+        for y in 0..height {
+            for x in 0..width {
+                screen.set_tile(x as i32, y as i32, 0);
+            }
+        }
+
+        return Framebuffer {width, height, screen};
+    }
+
+    pub fn size(&self) -> usize {
+        return self.width * self.height;
+    }
+
+    pub fn render(&self) -> String {
+        return self.screen.render();
+    }
+
+    fn position(&self, offset: usize) -> (i32, i32) {
+        return ((offset % self.width) as i32, (offset / self.width) as i32);
+    }
+}
+
+impl Device for Framebuffer {
+    fn read(&mut self, offset: usize) -> i32 {
+        let (x, y) = self.position(offset);
+        return self.screen.tile(x, y);
+    }
+
+    fn write(&mut self, offset: usize, value: i32) {
+        let (x, y) = self.position(offset);
+        self.screen.set_tile(x, y, value);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+use super::intcode::{Instruction, Observer, Verdict};
+use super::Memory;
+
+// Where each output came from, and where the program halted
+pub struct OutputSites {
+    pub sites: Vec<usize>,
+    pub halted_at: Option<usize>
+}
+
+impl OutputSites {
+    pub fn new() -> OutputSites {
+        return OutputSites {sites: Vec::new(), halted_at: None};
+    }
+}
+
+impl Observer for OutputSites {
+    fn after_instruction(&mut self, _memory: &Memory, address: usize, instruction: &Instruction) -> Verdict {
+        if instruction.opcode == 4 {
+            self.sites.push(address);
+        }
+        return Verdict::Continue;
+    }
+
+    fn halt(&mut self, memory: &Memory) {
+        self.halted_at = Some(memory.program_counter);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiagnosticError {
+    NoOutput,
+    // A test output that wasn't 0, which is how far off the result of the test was
+    FailedTest { index: usize, pc: usize, value: i32 },
+    // The diagnostic code has to be followed straight away by a halt
+    NotFinished { pc: usize }
+}
+
+impl fmt::Display for DiagnosticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiagnosticError::NoOutput => write!(f, "The program didn't output a diagnostic code!"),
+            DiagnosticError::FailedTest { index, pc, value } =>
+                write!(f, "Test {} failed: the output instruction at {} gave {} instead of 0!", index, pc, value),
+            DiagnosticError::NotFinished { pc } => write!(f, "The diagnostic code from the output instruction at {} wasn't followed by a halt!", pc)
+        }
+    }
+}
+
+// Day 5 diagnostic programs output a 0 for every test that passed, then a diagnostic code just before
+// halting. Returns the diagnostic code if the output follows that.
+pub fn check(output: &[i32], sites: &OutputSites) -> Result<i32, DiagnosticError> {
+    let (code, tests) = match output.split_last() {
+        None => return Err(DiagnosticError::NoOutput),
+        Some(split) => split
+    };
+
+    for (index, &value) in tests.iter().enumerate() {
+        if value != 0 {
+            return Err(DiagnosticError::FailedTest {index, pc: sites.sites[index], value});
+        }
+    }
+
+    let last = sites.sites[tests.len()];
+    if sites.halted_at != Some(last + 2) {
+        return Err(DiagnosticError::NotFinished {pc: last});
+    }
+
+    return Ok(*code);
+}
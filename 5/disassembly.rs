@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::Range;
+
+use super::intcode::{Instruction, ParameterMode};
+use super::{IntcodeComputer, Program};
+
+pub const HALT: i32 = 99;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Immediate(i32),
+    // Reads from, or writes to, the cell at this address
+    Position(usize)
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Immediate(value) => write!(f, "#{}", value),
+            Operand::Position(address) => write!(f, "[{}]", address)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DecodedInstruction {
+    pub address: usize,
+    pub opcode: u8,
+    pub operands: Vec<Operand>,
+    pub write_operands: Vec<u8>
+}
+
+impl DecodedInstruction {
+    pub fn mnemonic(&self) -> &'static str {
+        return match self.opcode {
+            1 => "add",
+            2 => "mul",
+            3 => "in",
+            4 => "out",
+            5 => "jnz",
+            6 => "jz",
+            7 => "lt",
+            8 => "eq",
+            9 => "arb",
+            10 => "host",
+            99 => "halt",
+            _ => "???"
+        };
+    }
+
+    pub fn cells(&self) -> Range<usize> {
+        return self.address..self.next_address();
+    }
+
+    pub fn next_address(&self) -> usize {
+        return self.address + self.operands.len() + 1;
+    }
+
+    pub fn is_halt(&self) -> bool {
+        return self.opcode == HALT as u8;
+    }
+
+    pub fn is_jump(&self) -> bool {
+        return self.opcode == 5 || self.opcode == 6;
+    }
+
+    // Whether a jump fires, if that only depends on immediate operands
+    pub fn constant_condition(&self) -> Option<bool> {
+        return match (self.opcode, self.operands.first()) {
+            (5, Some(&Operand::Immediate(value))) => Some(value != 0),
+            (6, Some(&Operand::Immediate(value))) => Some(value == 0),
+            _ => None
+        };
+    }
+
+    pub fn jump_target(&self) -> Option<Operand> {
+        return if self.is_jump() { Some(self.operands[1]) } else { None };
+    }
+
+    pub fn write_targets(&self) -> Vec<usize> {
+        return self.write_operands.iter()
+                   .filter_map(|&index| match self.operands[index as usize] {
+                       Operand::Position(address) => Some(address),
+                       Operand::Immediate(_) => None
+                   })
+                   .collect();
+    }
+
+    // Cells read through position mode operands, not counting the ones being written to
+    pub fn data_reads(&self) -> Vec<usize> {
+        return self.operands.iter()
+                   .enumerate()
+                   .filter(|(index, _)| !self.write_operands.contains(&(*index as u8)))
+                   .filter_map(|(_, operand)| match operand {
+                       Operand::Position(address) => Some(*address),
+                       Operand::Immediate(_) => None
+                   })
+                   .collect();
+    }
+
+    // The value written by an arithmetic or comparison instruction whose inputs are both immediate
+    pub fn constant_result(&self) -> Option<i32> {
+        let (a, b) = match (self.operands.first(), self.operands.get(1)) {
+            (Some(&Operand::Immediate(a)), Some(&Operand::Immediate(b))) => (a, b),
+            _ => return None
+        };
+
+        return match self.opcode {
+            1 => a.checked_add(b),
+            2 => a.checked_mul(b),
+            7 => Some(if a < b { 1 } else { 0 }),
+            8 => Some(if a == b { 1 } else { 0 }),
+            _ => None
+        };
+    }
+}
+
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for (index, operand) in self.operands.iter().enumerate() {
+            let separator = if self.write_operands.contains(&(index as u8)) { " ->" } else if index > 0 { "," } else { "" };
+            write!(f, "{} {}", separator, operand)?;
+        }
+        return Ok(());
+    }
+}
+
+// Decodes the instruction starting at the given address the same way the computer would, or None
+// if it isn't something the computer could execute
+pub fn decode(computer: &IntcodeComputer, program: &[i32], address: usize) -> Option<DecodedInstruction> {
+    let value = *program.get(address)?;
+    if value == HALT {
+        return Some(DecodedInstruction {address, opcode: HALT as u8, operands: Vec::new(), write_operands: Vec::new()});
+    }
+    if value < 0 {
+        return None;
+    }
+
+    let opcode = Instruction::decode_opcode(value);
+    let instruction = computer.instructions.get(&opcode)?;
+
+    let mut operands = Vec::new();
+    for i in 0..instruction.parameter_count {
+        let parameter = *program.get(address + i as usize + 1)?;
+        let operand = if instruction.write_parameters.contains(&i) {
+            let mode = Instruction::decode_parameter_mode(value, i, computer.profile).ok()?;
+            if parameter < 0 || mode == ParameterMode::Relative {
+                return None;
+            }
+            Operand::Position(parameter as usize)
+        } else {
+            match Instruction::decode_parameter_mode(value, i, computer.profile).ok()? {
+                ParameterMode::Immediate => Operand::Immediate(parameter),
+                ParameterMode::Position if parameter >= 0 => Operand::Position(parameter as usize),
+                // Where these point depends on the relative base when they run, so they can't be followed
+                ParameterMode::Position | ParameterMode::Relative => return None
+            }
+        };
+        operands.push(operand);
+    }
+
+    return Some(DecodedInstruction {address, opcode, operands, write_operands: instruction.write_parameters.clone()});
+}
+
+// Every instruction reachable from address 0, following both sides of each jump
+pub struct ControlFlow {
+    pub instructions: BTreeMap<usize, DecodedInstruction>,
+    // Jump target -> the jumps that can land there
+    pub jump_targets: BTreeMap<usize, BTreeSet<usize>>,
+    // Jumps whose target is read from a cell we can't work out the value of
+    pub unresolved_jumps: BTreeSet<usize>,
+    // Reachable instructions that something writes over, so they may not run as decoded here
+    pub modified_instructions: BTreeSet<usize>
+}
+
+impl ControlFlow {
+    // Whether every instruction that can run, and so every cell that can be written, is known
+    pub fn is_complete(&self) -> bool {
+        return self.unresolved_jumps.is_empty() && self.modified_instructions.is_empty();
+    }
+
+    pub fn write_targets(&self) -> BTreeSet<usize> {
+        return self.instructions.values().flat_map(|instruction| instruction.write_targets()).collect();
+    }
+
+    pub fn data_reads(&self) -> BTreeSet<usize> {
+        return self.instructions.values().flat_map(|instruction| instruction.data_reads()).collect();
+    }
+
+    // The values a cell can hold: what the program starts with, plus whatever gets written to it,
+    // as long as every write to it is a constant
+    pub fn possible_values(&self, program: &[i32], address: usize) -> Option<BTreeSet<i32>> {
+        // Memory grows to fit, so cells past the end of the program start out as 0
+        let mut values = BTreeSet::new();
+        values.insert(program.get(address).cloned().unwrap_or(0));
+
+        for instruction in self.instructions.values() {
+            if instruction.write_targets().contains(&address) {
+                values.insert(instruction.constant_result()?);
+            }
+        }
+
+        return Some(values);
+    }
+}
+
+pub fn trace_control_flow(computer: &IntcodeComputer, program: &Program) -> ControlFlow {
+    let mut control_flow = ControlFlow {
+        instructions: BTreeMap::new(),
+        jump_targets: BTreeMap::new(),
+        unresolved_jumps: BTreeSet::new(),
+        modified_instructions: BTreeSet::new()
+    };
+
+    let mut undecodable = BTreeSet::new();
+    let mut frontier = vec![0];
+    loop {
+        while let Some(address) = frontier.pop() {
+            if control_flow.instructions.contains_key(&address) || undecodable.contains(&address) {
+                continue;
+            }
+
+            let instruction = match decode(computer, program, address) {
+                None => {
+                    undecodable.insert(address);
+                    continue;
+                },
+                Some(instruction) => instruction
+            };
+
+            if let Some(Operand::Immediate(target)) = instruction.jump_target() {
+                if target >= 0 {
+                    control_flow.jump_targets.entry(target as usize).or_default().insert(address);
+                    if instruction.constant_condition() != Some(false) {
+                        frontier.push(target as usize);
+                    }
+                }
+            }
+
+            // A jump to itself leaves the pc where it was, so the computer moves on anyway
+            let jumps_to_itself = instruction.jump_target() == Some(Operand::Immediate(address as i32));
+            let falls_through = !instruction.is_halt() && (instruction.constant_condition() != Some(true) || jumps_to_itself);
+            if falls_through {
+                frontier.push(instruction.next_address());
+            }
+
+            control_flow.instructions.insert(address, instruction);
+        }
+
+        // Jumps through a cell can only be followed once we know everything that writes to that
+        // cell, so keep going until resolving them stops finding new code
+        control_flow.unresolved_jumps.clear();
+        let position_jumps: Vec<(usize, usize, usize)> = control_flow.instructions.values()
+            .filter(|instruction| instruction.constant_condition() != Some(false))
+            .filter_map(|instruction| match instruction.jump_target() {
+                Some(Operand::Position(cell)) => Some((instruction.address, instruction.next_address(), cell)),
+                _ => None
+            })
+            .collect();
+
+        for (address, next, cell) in position_jumps {
+            match control_flow.possible_values(program, cell) {
+                None => { control_flow.unresolved_jumps.insert(address); },
+                Some(targets) => {
+                    for target in targets.into_iter().filter(|&target| target >= 0).map(|target| target as usize) {
+                        let target = if target == address { next } else { target };
+                        control_flow.jump_targets.entry(target).or_default().insert(address);
+                        if !control_flow.instructions.contains_key(&target) && !undecodable.contains(&target) {
+                            frontier.push(target);
+                        }
+                    }
+                }
+            };
+        }
+
+        if frontier.is_empty() {
+            break;
+        }
+    }
+
+    // Something that doesn't decode is only a problem if it might have been patched into something that does
+    let written = control_flow.write_targets();
+    control_flow.modified_instructions = control_flow.instructions.values()
+        .filter(|instruction| instruction.cells().any(|cell| written.contains(&cell)))
+        .map(|instruction| instruction.address)
+        .chain(undecodable.into_iter().filter(|address| written.contains(address)))
+        .collect();
+
+    return control_flow;
+}
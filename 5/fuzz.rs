@@ -0,0 +1,235 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use super::binary::Image;
+use super::generator::Generated;
+use super::intcode::{Instruction, Observer, ParameterMode, Verdict};
+use super::{ExecutionState, IntcodeComputer, Memory, Profile};
+
+pub struct Failure {
+    pub property: &'static str,
+    pub detail: String
+}
+
+fn failure(property: &'static str, detail: String) -> Result<(), Failure> {
+    return Err(Failure {property, detail});
+}
+
+// Works out what each instruction should do before it runs, and checks that is what it did. Also
+// watches for writes the generator promises never to make.
+struct Invariants {
+    profile: Profile,
+    data: Range<usize>,
+    // The address and value the running instruction should write, what it should output, and where
+    // it should leave the pc
+    expected_write: Option<(usize, i32)>,
+    expected_output: Option<i32>,
+    expected_pc: Option<usize>,
+    violations: Vec<(&'static str, String)>
+}
+
+impl Invariants {
+    fn new(profile: Profile, data: Range<usize>) -> Invariants {
+        return Invariants {profile, data, expected_write: None, expected_output: None, expected_pc: None, violations: Vec::new()};
+    }
+
+    fn violation(&mut self, property: &'static str, detail: String) -> Verdict {
+        self.violations.push((property, detail));
+        return Verdict::Abort;
+    }
+}
+
+fn value_at(memory: &Memory, address: i32) -> i32 {
+    return memory.values.get(address as usize).cloned().unwrap_or(0);
+}
+
+// The address a parameter refers to, and the value there
+fn parameter(memory: &Memory, index: u8, profile: Profile) -> (i32, i32) {
+    let address = memory.parameter_address(index, profile).unwrap_or(0);
+    return (address, value_at(memory, address));
+}
+
+impl Observer for Invariants {
+    fn before_instruction(&mut self, memory: &Memory, instruction: &Instruction) -> Verdict {
+        let opcode = memory.get_opcode();
+        for &parameter in &instruction.write_parameters {
+            if let Ok(ParameterMode::Immediate) = Instruction::decode_parameter_mode(opcode, parameter, self.profile) {
+                let detail = format!("instruction {} at {} writes through immediate parameter {}", opcode, memory.program_counter, parameter);
+                return self.violation("no immediate mode writes", detail);
+            }
+        }
+
+        let pc = memory.program_counter;
+        let profile = self.profile;
+        let next = pc + instruction.parameter_count as usize + 1;
+        let value = |index| parameter(memory, index, profile).1;
+        let destination = |index| parameter(memory, index, profile).0 as usize;
+        self.expected_write = match instruction.opcode {
+            1 => Some((destination(2), value(0) + value(1))),
+            2 => Some((destination(2), value(0) * value(1))),
+            7 => Some((destination(2), (value(0) < value(1)) as i32)),
+            8 => Some((destination(2), (value(0) == value(1)) as i32)),
+            _ => None
+        };
+        self.expected_output = if instruction.opcode == 4 { Some(value(0)) } else { None };
+        self.expected_pc = match instruction.opcode {
+            5 => Some(if value(0) != 0 { value(1) as usize } else { next }),
+            6 => Some(if value(0) == 0 { value(1) as usize } else { next }),
+            // Host calls and input are left to the other properties
+            3 | 10 => None,
+            _ => Some(next)
+        };
+        return Verdict::Continue;
+    }
+
+    fn after_instruction(&mut self, memory: &Memory, address: usize, instruction: &Instruction) -> Verdict {
+        if let Some((destination, value)) = self.expected_write.take() {
+            return self.violation("instructions do what they say", format!("instruction {} at {} should have written {} to {}", instruction.opcode, address, value, destination));
+        }
+        if let Some(value) = self.expected_output.take() {
+            return self.violation("instructions do what they say", format!("instruction at {} should have output {}", address, value));
+        }
+        if let Some(pc) = self.expected_pc.take() {
+            if pc != memory.program_counter {
+                return self.violation("instructions do what they say", format!("instruction {} at {} should have gone to {}, not {}", instruction.opcode, address, pc, memory.program_counter));
+            }
+        }
+        return Verdict::Continue;
+    }
+
+    fn memory_write(&mut self, pc: usize, address: usize, _old: i32, new: i32) -> Verdict {
+        if !self.data.contains(&address) {
+            return self.violation("writes stay in the data", format!("instruction at {} wrote to {}, outside the data at {:?}", pc, address, self.data));
+        }
+        if let Some(expected) = self.expected_write.take() {
+            if expected != (address, new) {
+                return self.violation("instructions do what they say", format!("instruction at {} wrote {} to {} instead of {} to {}", pc, new, address, expected.1, expected.0));
+            }
+        }
+        return Verdict::Continue;
+    }
+
+    fn output(&mut self, _step: usize, value: i32) -> Verdict {
+        if let Some(expected) = self.expected_output.take() {
+            if expected != value {
+                return self.violation("instructions do what they say", format!("output {} instead of {}", value, expected));
+            }
+        }
+        return Verdict::Continue;
+    }
+}
+
+// Runs a generated program a few different ways, checking the computer does what every valid
+// program should make it do
+pub fn check(generated: &Generated, profile: Profile, make_computer: &dyn Fn(Profile) -> IntcodeComputer) -> Result<(), Failure> {
+    let invariants = Arc::new(Mutex::new(Invariants::new(profile, generated.data.clone())));
+    let mut computer = make_computer(profile);
+    computer.add_observer(invariants.clone());
+
+    let mut memory = Memory::initialize(&generated.program);
+    let mut output = Vec::new();
+    let result = computer.resume(&mut memory, &mut generated.input.iter(), &mut output);
+
+    if let Ok(invariants) = invariants.lock() {
+        if let Some((property, detail)) = invariants.violations.first() {
+            return failure(property, detail.clone());
+        }
+    }
+    match result {
+        Err(why) => return failure("halts", why.to_string()),
+        Ok(ExecutionState::Halted) => (),
+        Ok(state) => return failure("halts", format!("stopped {:?} at {}", state, memory.program_counter))
+    };
+
+    if memory.values.get(memory.program_counter) != Some(&99) {
+        return failure("halts on 99", format!("halted at {}, which holds {:?}", memory.program_counter, memory.values.get(memory.program_counter)));
+    }
+    if memory.steps > generated.max_steps {
+        return failure("jumps only go forwards", format!("ran {} instructions, but there are only {}", memory.steps, generated.max_steps));
+    }
+
+    // Observing a program shouldn't change what it does
+    let computer = make_computer(profile);
+    let mut second_output = Vec::new();
+    let second = computer.execute(&generated.program, &generated.input, &mut second_output);
+    if second_output != output || second.values != memory.values || second.steps != memory.steps {
+        return failure("runs are repeatable", format!("output {:?} then {:?}, after {} then {} steps", output, second_output, memory.steps, second.steps));
+    }
+
+    // Giving input as it is asked for should be the same as giving it all up front
+    let mut piecemeal = Memory::initialize(&generated.program);
+    let mut piecemeal_output = Vec::new();
+    let mut next: &[i32] = &[];
+    let mut given = 0;
+    loop {
+        match computer.resume(&mut piecemeal, &mut next.iter(), &mut piecemeal_output) {
+            Err(why) => return failure("input can be given one value at a time", why.to_string()),
+            Ok(ExecutionState::Halted) => break,
+            Ok(ExecutionState::AwaitingInput) if given < generated.input.len() => {
+                next = &generated.input[given..given + 1];
+                given += 1;
+            },
+            Ok(state) => return failure("input can be given one value at a time", format!("stopped {:?} at {}", state, piecemeal.program_counter))
+        };
+    }
+    if piecemeal_output != output || piecemeal.values != memory.values {
+        return failure("input can be given one value at a time", format!("output {:?} instead of {:?}", piecemeal_output, output));
+    }
+
+    let image = Image::program(profile, generated.program.clone());
+    match Image::decode(&image.encode()) {
+        Err(why) => return failure("binary images round trip", why.to_string()),
+        Ok(decoded) if decoded != image => return failure("binary images round trip", String::from("decoded image differs")),
+        Ok(_) => ()
+    };
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::generator::{Generated, Generator};
+    use super::super::{make_computer, Profile};
+    use super::check;
+
+    const PROGRAMS: i32 = 300;
+
+    fn check_programs(profile: Profile) {
+        for seed in 0..PROGRAMS {
+            let generated = Generator::new(profile, seed, vec![1, 2]).generate();
+            if let Err(failure) = check(&generated, profile, &make_computer) {
+                panic!("{} seed {}: {} failed: {}\n    program {:?}\n    input {:?}", profile.name(), seed, failure.property, failure.detail, generated.program, generated.input);
+            }
+        }
+    }
+
+    #[test]
+    fn basic_programs_behave() {
+        check_programs(Profile::Basic);
+    }
+
+    #[test]
+    fn diagnostic_programs_behave() {
+        check_programs(Profile::Diagnostic);
+    }
+
+    #[test]
+    fn extended_programs_behave() {
+        check_programs(Profile::Extended);
+    }
+
+    #[test]
+    fn hosted_programs_behave() {
+        check_programs(Profile::Hosted);
+    }
+
+    // Generated programs never write in immediate mode, so this checks the property can still fail
+    #[test]
+    fn immediate_mode_writes_are_caught() {
+        let generated = Generated {program: vec![11101, 1, 1, 5, 99, 0], input: Vec::new(), data: 5..6, max_steps: 2};
+        match check(&generated, Profile::Diagnostic, &make_computer) {
+            Err(failure) => assert_eq!(failure.property, "no immediate mode writes"),
+            Ok(()) => panic!("an immediate mode write wasn't caught")
+        };
+    }
+}
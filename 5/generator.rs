@@ -0,0 +1,235 @@
+use std::ops::Range;
+
+use super::devices::Random;
+use super::intcode::{Device, ParameterMode, HOST_CALL};
+use super::{Profile, Program};
+
+// Values are kept below this so nothing a generated program does can overflow
+const LIMIT: i64 = 1_000_000;
+const INPUT_LIMIT: i32 = 100;
+
+// A cell of a program before the data and the instructions have been placed
+#[derive(Clone, Copy)]
+enum Cell {
+    Value(i32),
+    // Offset into the data after the code
+    Data(usize),
+    // The address of the nth instruction, or the final halt
+    Label(usize)
+}
+
+pub struct Generated {
+    pub program: Program,
+    // Enough input for every input instruction, whichever ones end up running
+    pub input: Vec<i32>,
+    // Where the data is. Generated programs never write anywhere else.
+    pub data: Range<usize>,
+    // Since jumps only go forwards, no run can take more steps than this
+    pub max_steps: usize
+}
+
+// Makes random programs that are valid for a profile: every opcode and mode is one the profile has,
+// every address is inside the program, and every jump is forwards, so they always halt on the 99
+// at the end of the code. The data after the code starts with a cell that always holds 1, which is
+// never written to.
+pub struct Generator {
+    profile: Profile,
+    random: Random,
+    // Host functions that may be called. Their results are assumed to be no bigger than the sum of
+    // their arguments, which is true of sum and max.
+    host_functions: Vec<usize>,
+    // How big each data cell could be by now
+    bounds: Vec<i64>,
+    code: Vec<Cell>,
+    labels: Vec<usize>,
+    inputs: usize
+}
+
+impl Generator {
+    pub fn new(profile: Profile, seed: i32, host_functions: Vec<usize>) -> Generator {
+        let host_functions = if profile.allows_host_calls() { host_functions } else { Vec::new() };
+        return Generator {profile, random: Random::new(seed), host_functions, bounds: Vec::new(), code: Vec::new(), labels: Vec::new(), inputs: 0};
+    }
+
+    pub fn generate(&mut self) -> Generated {
+        let instructions = 1 + self.below(12);
+        let data_size = 4 + self.below(9);
+
+        let mut data = vec![1];
+        for _ in 1..data_size {
+            data.push(self.between(-INPUT_LIMIT, INPUT_LIMIT));
+        }
+        self.bounds = data.iter().map(|value| (*value as i64).abs()).collect();
+        self.code = Vec::new();
+        self.labels = Vec::new();
+        self.inputs = 0;
+
+        // Relative mode addresses are all worked out from the relative base being the start of the data
+        let relative = self.profile.supports(ParameterMode::Relative);
+        if relative {
+            self.code.extend(&[Cell::Value(109), Cell::Data(0)]);
+        }
+
+        for index in 0..instructions {
+            self.labels.push(self.code.len());
+
+            // Moving the relative base is only safe when it moves straight back, with nothing in
+            // between that could jump away
+            if relative && self.below(6) == 0 {
+                let shift = self.below(data_size);
+                self.code.extend(&[Cell::Value(109), Cell::Value(shift as i32)]);
+                self.instruction(index, instructions, shift, false);
+                self.code.extend(&[Cell::Value(109), Cell::Value(-(shift as i32))]);
+            } else {
+                self.instruction(index, instructions, 0, true);
+            }
+        }
+        self.labels.push(self.code.len());
+        self.code.push(Cell::Value(99));
+
+        let data_start = self.code.len();
+        let mut program: Program = self.code.iter()
+                                       .map(|cell| match *cell {
+                                           Cell::Value(value) => value,
+                                           Cell::Data(offset) => (data_start + offset) as i32,
+                                           Cell::Label(index) => self.labels[index] as i32
+                                       })
+                                       .collect();
+        program.extend(&data);
+
+        let input = (0..self.inputs).map(|_| self.between(-INPUT_LIMIT, INPUT_LIMIT)).collect();
+        return Generated {program, input, data: data_start..data_start + data_size, max_steps: self.labels.len() * 3};
+    }
+
+    fn below(&mut self, limit: usize) -> usize {
+        return self.random.read(0) as usize % limit;
+    }
+
+    fn between(&mut self, low: i32, high: i32) -> i32 {
+        return low + self.below((high - low + 1) as usize) as i32;
+    }
+
+    fn opcodes(&self, can_jump: bool) -> Vec<u8> {
+        let mut opcodes = vec![1, 2];
+        if self.profile != Profile::Basic {
+            opcodes.extend(&[3, 4, 7, 8]);
+            if can_jump {
+                opcodes.extend(&[5, 6]);
+            }
+        }
+        // Opcode 9 only appears around other instructions, since it is only safe there
+        if !self.host_functions.is_empty() {
+            opcodes.push(HOST_CALL);
+        }
+        return opcodes;
+    }
+
+    fn mode(&mut self, can_be_immediate: bool) -> ParameterMode {
+        let mut modes = vec![ParameterMode::Position];
+        for mode in &[ParameterMode::Immediate, ParameterMode::Relative] {
+            if self.profile.supports(*mode) && (can_be_immediate || *mode != ParameterMode::Immediate) {
+                modes.push(*mode);
+            }
+        }
+        return modes[self.below(modes.len())];
+    }
+
+    // A parameter reading from a data cell, or an immediate value, with how big it could be
+    fn read(&mut self, shift: usize) -> (ParameterMode, Cell, i64) {
+        let mode = self.mode(true);
+        return match mode {
+            ParameterMode::Immediate => {
+                let value = self.between(-9, 9);
+                (mode, Cell::Value(value), (value as i64).abs())
+            },
+            _ => {
+                let offset = self.below(self.bounds.len());
+                (mode, self.data_cell(mode, offset, shift), self.bounds[offset])
+            }
+        };
+    }
+
+    // Reading the cell that always holds 1
+    fn one(&mut self, shift: usize) -> (ParameterMode, Cell, i64) {
+        let mode = self.mode(false);
+        return (mode, self.data_cell(mode, 0, shift), 1);
+    }
+
+    fn data_cell(&self, mode: ParameterMode, offset: usize, shift: usize) -> Cell {
+        return match mode {
+            ParameterMode::Relative => Cell::Value(offset as i32 - shift as i32),
+            _ => Cell::Data(offset)
+        };
+    }
+
+    // Anywhere in the data but the cell holding 1
+    fn write(&mut self, shift: usize, bound: i64) -> (ParameterMode, Cell) {
+        let mode = self.mode(false);
+        let offset = 1 + self.below(self.bounds.len() - 1);
+        self.bounds[offset] = self.bounds[offset].max(bound);
+        return (mode, self.data_cell(mode, offset, shift));
+    }
+
+    fn instruction(&mut self, index: usize, instructions: usize, shift: usize, can_jump: bool) {
+        let opcodes = self.opcodes(can_jump);
+        let opcode = opcodes[self.below(opcodes.len())];
+
+        let parameters = match opcode {
+            1 | 2 | 7 | 8 => {
+                let (mut first, mut second) = (self.read(shift), self.read(shift));
+                let bound = match opcode {
+                    1 => first.2 + second.2,
+                    2 => first.2 * second.2,
+                    _ => 1
+                };
+                if bound > LIMIT {
+                    // 1 + 1 or 1 * 1 can't get too big
+                    first = self.one(shift);
+                    second = self.one(shift);
+                }
+                let bound = if opcode == 1 { first.2 + second.2 } else { first.2 * second.2 };
+                let (mode, cell) = self.write(shift, if opcode >= 7 { 1 } else { bound });
+                vec![(first.0, first.1), (second.0, second.1), (mode, cell)]
+            },
+            3 => {
+                self.inputs += 1;
+                vec![self.write(shift, INPUT_LIMIT as i64)]
+            },
+            4 => {
+                let (mode, cell, _) = self.read(shift);
+                vec![(mode, cell)]
+            },
+            5 | 6 => {
+                let (mode, cell, _) = self.read(shift);
+                let target = index + 1 + self.below(instructions - index);
+                vec![(mode, cell), (ParameterMode::Immediate, Cell::Label(target))]
+            },
+            _ => {
+                // The arguments are the cells from the relative base on
+                let choice = self.below(self.host_functions.len());
+                let function = self.host_functions[choice];
+                let available = self.bounds.len() - shift;
+                let mut count = 1 + self.below(available.min(4));
+                if self.bounds[shift..shift + count].iter().sum::<i64>() > LIMIT {
+                    count = 1;
+                }
+                let bound = self.bounds[shift..shift + count].iter().sum();
+                let destination = self.write(shift, bound);
+                vec![(ParameterMode::Immediate, Cell::Value(function as i32)), (ParameterMode::Immediate, Cell::Value(count as i32)), destination]
+            }
+        };
+
+        let mut value = opcode as i32;
+        for (position, (mode, _)) in parameters.iter().enumerate() {
+            let digit = match mode {
+                ParameterMode::Position => 0,
+                ParameterMode::Immediate => 1,
+                ParameterMode::Relative => 2
+            };
+            value += digit * 10i32.pow(position as u32 + 2);
+        }
+
+        self.code.push(Cell::Value(value));
+        self.code.extend(parameters.iter().map(|(_, cell)| *cell));
+    }
+}
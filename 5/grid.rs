@@ -0,0 +1,96 @@
+use std::fmt;
+use std::ops;
+
+// Points and directions on a grid, shared with the wire tracer in day 3
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32
+}
+
+impl Point {
+    pub fn new(x: i32, y: i32) -> Point {
+        return Point {x, y};
+    }
+}
+
+impl ops::Add<Point> for Point {
+    type Output = Point;
+    fn add(self, other: Point) -> Point {
+        return Point::new(self.x + other.x, self.y + other.y);
+    }
+}
+
+impl ops::Mul<i32> for Point {
+    type Output = Point;
+    fn mul(self, scale: i32) -> Point {
+        return Point::new(self.x * scale, self.y * scale);
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right
+}
+
+impl Direction {
+    pub fn delta(&self) -> Point {
+        return match self {
+            Direction::Up => Point::new(0, 1),
+            Direction::Down => Point::new(0, -1),
+            Direction::Left => Point::new(-1, 0),
+            Direction::Right => Point::new(1, 0)
+        };
+    }
+
+    pub fn turn_left(&self) -> Direction {
+        return match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up
+        };
+    }
+
+    pub fn turn_right(&self) -> Direction {
+        return match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up
+        };
+    }
+}
+
+// Renders every point between the extremes of the given points, top row (largest y) first
+pub fn render<F>(points: &[Point], glyph: F) -> String
+where F: Fn(Point) -> char
+{
+    if points.is_empty() {
+        return String::new();
+    }
+
+    let min_x = points.iter().map(|point| point.x).min().unwrap();
+    let max_x = points.iter().map(|point| point.x).max().unwrap();
+    let min_y = points.iter().map(|point| point.y).min().unwrap();
+    let max_y = points.iter().map(|point| point.y).max().unwrap();
+
+    let mut text = String::new();
+    for y in (min_y..=max_y).rev() {
+        for x in min_x..=max_x {
+            text.push(glyph(Point::new(x, y)));
+        }
+        text.push('\n');
+    }
+
+    return text;
+}
@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fmt::Write;
+use std::ops::Range;
+
+use super::disassembly;
+use super::intcode::{Instruction, Observer, Verdict};
+use super::{IntcodeComputer, Memory};
+
+const COLUMNS: usize = 8;
+
+// The pc of the last instruction to write to each cell, and where every instruction that ran started
+pub struct MemoryHistory {
+    pub writers: BTreeMap<usize, usize>,
+    pub executed: BTreeSet<usize>
+}
+
+impl MemoryHistory {
+    pub fn new() -> MemoryHistory {
+        return MemoryHistory {writers: BTreeMap::new(), executed: BTreeSet::new()};
+    }
+}
+
+impl Observer for MemoryHistory {
+    fn after_instruction(&mut self, _memory: &Memory, address: usize, _instruction: &Instruction) -> Verdict {
+        self.executed.insert(address);
+        return Verdict::Continue;
+    }
+
+    fn memory_write(&mut self, pc: usize, address: usize, _old: i32, _new: i32) -> Verdict {
+        self.writers.insert(address, pc);
+        return Verdict::Continue;
+    }
+}
+
+// Rows of cells, each ending with the instructions that start in it. Instructions are the ones that
+// ran, or if nothing has, the ones found by following the code from address 0. Each is decoded from
+// what is in memory now, so patched instructions show what they have become.
+pub fn dump(computer: &IntcodeComputer, memory: &Memory, history: &MemoryHistory, range: Range<usize>) -> String {
+    let instructions = if history.executed.is_empty() {
+        disassembly::trace_control_flow(computer, &memory.values).instructions.keys().cloned().collect()
+    } else {
+        history.executed.clone()
+    };
+    let end = range.end.min(memory.values.len());
+    let start = range.start - range.start % COLUMNS;
+
+    let mut text = String::new();
+    for row in (start..end).step_by(COLUMNS) {
+        let mut line = format!("{:>6}:", row);
+        for address in row..row + COLUMNS {
+            match memory.values.get(address) {
+                Some(value) if range.contains(&address) => write!(line, " {:>8}", value).unwrap(),
+                _ => write!(line, " {:>8}", "").unwrap()
+            };
+        }
+
+        let hints: Vec<String> = instructions.range(row..row + COLUMNS)
+                                             .map(|&address| match disassembly::decode(computer, &memory.values, address) {
+                                                 None => format!("{} ???", address),
+                                                 Some(instruction) => format!("{} {}", address, instruction.mnemonic())
+                                             })
+                                             .collect();
+        if !hints.is_empty() {
+            write!(line, "  | {}", hints.join(", ")).unwrap();
+        }
+        writeln!(text, "{}", line.trim_end()).unwrap();
+    }
+    return text;
+}
+
+pub struct Change {
+    pub address: usize,
+    pub before: i32,
+    pub after: i32,
+    // The last instruction to write the cell in each memory, if any did
+    pub writers: (Option<usize>, Option<usize>)
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>6}: {:>8} -> {:>8}", self.address, self.before, self.after)?;
+        match self.writers {
+            (None, None) => Ok(()),
+            (None, Some(after)) => write!(f, "  written at {}", after),
+            (Some(before), None) => write!(f, "  written at {} before, untouched after", before),
+            (Some(before), Some(after)) => write!(f, "  written at {} before, {} after", before, after)
+        }
+    }
+}
+
+// Cells in range that differ between two memories, such as a program before and after running, or
+// two runs with different input. Cells past the end of either memory count as zero.
+pub fn diff(before: (&Memory, &MemoryHistory), after: (&Memory, &MemoryHistory), range: Range<usize>) -> Vec<Change> {
+    let length = before.0.values.len().max(after.0.values.len());
+    let value = |memory: &Memory, address: usize| memory.values.get(address).cloned().unwrap_or(0);
+
+    return (range.start..range.end.min(length))
+               .filter(|&address| value(before.0, address) != value(after.0, address))
+               .map(|address| Change {
+                   address,
+                   before: value(before.0, address),
+                   after: value(after.0, address),
+                   writers: (before.1.writers.get(&address).cloned(), after.1.writers.get(&address).cloned())
+               })
+               .collect();
+}
@@ -0,0 +1,706 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParameterMode {
+    Immediate,
+    Position,
+    // Like position mode, but the address is offset by the relative base
+    Relative
+}
+
+// Which generation of Intcode programs a computer understands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    // Day 2: add, multiply and halt, with every parameter in position mode
+    Basic,
+    // Day 5: adds input, output, jumps and comparisons, with immediate mode parameters
+    Diagnostic,
+    // Day 9: adds relative mode parameters and an instruction to move the relative base
+    Extended,
+    // Extended, plus an instruction that calls functions provided by whatever is running the program
+    Hosted
+}
+
+impl Profile {
+    pub fn parse(name: &str) -> Option<Profile> {
+        return match name {
+            "basic" => Some(Profile::Basic),
+            "diagnostic" => Some(Profile::Diagnostic),
+            "extended" => Some(Profile::Extended),
+            "hosted" => Some(Profile::Hosted),
+            _ => None
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Profile::Basic => "basic",
+            Profile::Diagnostic => "diagnostic",
+            Profile::Extended => "extended",
+            Profile::Hosted => "hosted"
+        };
+    }
+
+    pub fn allows_host_calls(&self) -> bool {
+        return *self == Profile::Hosted;
+    }
+
+    pub fn supports(&self, mode: ParameterMode) -> bool {
+        return match (self, mode) {
+            (_, ParameterMode::Position) => true,
+            (Profile::Basic, _) => false,
+            (Profile::Diagnostic, ParameterMode::Relative) => false,
+            _ => true
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeError {
+    MissingInput,
+    UnknownOpcode(u8),
+    UnknownParameterMode(i32),
+    UnknownHostFunction(i32),
+    HostFunctionFailed { name: String, message: String },
+    NegativeAddress(i64),
+    // An observer asked to stop at the instruction at this address
+    Aborted(usize)
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::MissingInput => write!(f, "Ran out of input!"),
+            IntcodeError::UnknownOpcode(opcode) => write!(f, "Missing instruction definition for opcode {}!", opcode),
+            IntcodeError::UnknownParameterMode(mode) => write!(f, "Unknown parameter mode: {}!", mode),
+            IntcodeError::UnknownHostFunction(index) => write!(f, "No host function registered at index {}!", index),
+            IntcodeError::HostFunctionFailed { name, message } => write!(f, "Host function {} failed: {}!", name, message),
+            IntcodeError::NegativeAddress(address) => write!(f, "Tried to access negative address {}!", address),
+            IntcodeError::Aborted(address) => write!(f, "Aborted by an observer at {}!", address)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExecutionState {
+    Halted,
+    AwaitingInput,
+    // An observer asked to stop; resuming carries on from the next instruction
+    Paused
+}
+
+pub type InstructionHandler = Box<dyn Fn(Vec<i32>, &mut Memory, &mut dyn Iterator<Item = &i32>, &mut Vec<i32>) -> Result<(), IntcodeError> + Send + Sync>;
+
+pub const HOST_CALL: u8 = 10;
+
+pub struct Instruction {
+    pub opcode: u8,
+    pub parameter_count: u8,
+    pub write_parameters: Vec<u8>,
+    handler: InstructionHandler
+}
+
+impl Instruction {
+
+    pub fn new_with_writes<F>(opcode: u8, parameter_count: u8, write_parameters: Vec<u8>, handler: F) -> Instruction
+    where F: Fn(Vec<i32>, &mut Memory, &mut dyn Iterator<Item = &i32>, &mut Vec<i32>) -> Result<(), IntcodeError> + Send + Sync + 'static
+    {
+        return Instruction{opcode, parameter_count, write_parameters, handler: Box::new(handler)};
+    }
+
+    pub fn new<F>(opcode: u8, parameter_count: u8, handler: F) -> Instruction
+    where F: Fn(Vec<i32>, &mut Memory, &mut dyn Iterator<Item = &i32>, &mut Vec<i32>) -> Result<(), IntcodeError> + Send + Sync + 'static
+    {
+        return Instruction::new_with_writes(opcode, parameter_count, Vec::new(), handler);
+    }
+
+    pub fn decode_parameter_mode(value: i32, parameter_position: u8, profile: Profile) -> Result<ParameterMode, IntcodeError> {
+        let mut mode = value;
+
+        // We need to get rid of the first two digits as well, so we divide 2 extra times
+        for _ in 0..parameter_position + 2 {
+            mode /= 10;
+        }
+
+        let mode_value = match mode % 10 {
+            0 => ParameterMode::Position,
+            1 => ParameterMode::Immediate,
+            2 => ParameterMode::Relative,
+            _ => return Err(IntcodeError::UnknownParameterMode(mode % 10))
+        };
+
+        if !profile.supports(mode_value) {
+            return Err(IntcodeError::UnknownParameterMode(mode % 10));
+        }
+
+        return Ok(mode_value);
+    }
+
+    pub fn decode_opcode(value: i32) -> u8 {
+        return (value % 100) as u8;
+    }
+
+    fn execute(&self, memory: &mut Memory, input: &mut dyn Iterator<Item = &i32>, output: &mut Vec<i32>, profile: Profile, trace: bool) -> Result<(), IntcodeError> {
+        let mut parameters = Vec::new();
+        let opcode = memory.get_opcode();
+        for i in 0..self.parameter_count {
+            let parameter_mode = Instruction::decode_parameter_mode(opcode, i, profile)?;
+            let address = (memory.program_counter as i32) + (i as i32) + 1;
+            let value = if self.write_parameters.contains(&i) {
+                            // Handlers are given the address to write to, rather than what is there now
+                            memory.write_address(address, parameter_mode)?
+                        } else {
+                            memory.read(address, parameter_mode)?
+                        };
+
+            parameters.push(value);
+        }
+
+        if trace {
+            println!("Executing {} {:?}", opcode, parameters);
+        }
+
+        return (self.handler)(parameters, memory, input, output);
+    }
+}
+
+pub type Program = Vec<i32>;
+
+// Hardware mapped to a range of memory. Reading or writing a cell in that range calls into the
+// device instead, with the offset of the cell from the start of the range.
+pub trait Device {
+    fn read(&mut self, offset: usize) -> i32;
+    fn write(&mut self, offset: usize, value: i32);
+}
+
+// Devices are shared rather than copied when memory is cloned, the same way real hardware would be
+pub type SharedDevice = Arc<Mutex<dyn Device + Send>>;
+
+// What an observer wants the computer to do next
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    Continue,
+    Pause,
+    Abort
+}
+
+// Something watching a computer run. The memory callbacks are for the instruction that just ran,
+// so a Pause from any of them, or from after_instruction, stops before the next instruction. A
+// Pause from before_instruction stops before that instruction runs, and will be asked again when
+// execution resumes.
+pub trait Observer {
+    fn before_instruction(&mut self, _memory: &Memory, _instruction: &Instruction) -> Verdict {
+        return Verdict::Continue;
+    }
+
+    fn after_instruction(&mut self, _memory: &Memory, _address: usize, _instruction: &Instruction) -> Verdict {
+        return Verdict::Continue;
+    }
+
+    // A cell read through a position or relative mode parameter
+    fn memory_read(&mut self, _pc: usize, _address: usize, _value: i32) -> Verdict {
+        return Verdict::Continue;
+    }
+
+    fn memory_write(&mut self, _pc: usize, _address: usize, _old: i32, _new: i32) -> Verdict {
+        return Verdict::Continue;
+    }
+
+    // step is how many instructions had run before the one doing the input or output
+    fn input(&mut self, _step: usize, _value: i32) -> Verdict {
+        return Verdict::Continue;
+    }
+
+    fn output(&mut self, _step: usize, _value: i32) -> Verdict {
+        return Verdict::Continue;
+    }
+
+    fn halt(&mut self, _memory: &Memory) {
+    }
+}
+
+pub type SharedObserver = Arc<Mutex<dyn Observer + Send>>;
+
+#[derive(Clone, Copy, Debug)]
+enum MemoryEvent {
+    Read { address: usize, value: i32 },
+    Write { address: usize, old: i32, new: i32 },
+    Input(i32),
+    Output(i32)
+}
+
+#[derive(Clone)]
+pub struct Memory {
+    pub program_counter: usize,
+    pub relative_base: i32,
+    pub values: Vec<i32>,
+    // How many instructions have run so far
+    pub steps: usize,
+    devices: Vec<(Range<usize>, SharedDevice)>,
+    // What the current instruction has done so far, if anything is observing it
+    observed: bool,
+    events: Vec<MemoryEvent>,
+    // Whether observers have been told about the halt the pc is on, so resuming doesn't tell them again
+    halted: bool
+}
+
+impl Memory {
+    pub fn initialize(program: &Program) -> Memory {
+        return Memory {
+            program_counter: 0,
+            relative_base: 0,
+            values: program.clone(),
+            steps: 0,
+            devices: Vec::new(),
+            observed: false,
+            events: Vec::new(),
+            halted: false
+        };
+    }
+
+    pub fn attach_device(&mut self, addresses: Range<usize>, device: SharedDevice) {
+        if let Some((existing, _)) = self.devices.iter().find(|(existing, _)| existing.start < addresses.end && addresses.start < existing.end) {
+            panic!("Can't attach a device to {:?}, since it overlaps the device at {:?}", addresses, existing);
+        }
+        self.devices.push((addresses, device));
+    }
+
+    // The device mapped to an address, and how far into its range the address is
+    fn device_at(&self, address: usize) -> Option<(MutexGuard<'_, dyn Device + Send + 'static>, usize)> {
+        let (addresses, device) = self.devices.iter().find(|(addresses, _)| addresses.contains(&address))?;
+        return match device.lock() {
+            Err(why) => panic!("Device at {:?} is unusable: {}", addresses, why),
+            Ok(device) => Some((device, address - addresses.start))
+        };
+    }
+
+    // Instructions that consume input or produce output call these so observers hear about it
+    pub fn notify_input(&mut self, value: i32) {
+        if self.observed {
+            self.events.push(MemoryEvent::Input(value));
+        }
+    }
+
+    pub fn notify_output(&mut self, value: i32) {
+        if self.observed {
+            self.events.push(MemoryEvent::Output(value));
+        }
+    }
+
+    // The cell a position or relative mode parameter refers to
+    fn address(&self, position: i32, mode: ParameterMode) -> Result<i32, IntcodeError> {
+        let parameter = self.value_at(position)?;
+        return match mode {
+            ParameterMode::Relative => Ok(self.relative_base + parameter),
+            _ => Ok(parameter)
+        };
+    }
+
+    // The cell a parameter of the instruction at the pc refers to, which for an immediate parameter
+    // is the parameter itself. Nothing is read from it, so observers aren't told.
+    pub fn parameter_address(&self, index: u8, profile: Profile) -> Result<i32, IntcodeError> {
+        let position = (self.program_counter + 1 + index as usize) as i32;
+        return match Instruction::decode_parameter_mode(self.get_opcode(), index, profile)? {
+            ParameterMode::Immediate => Ok(position),
+            mode => self.address(position, mode)
+        };
+    }
+
+    fn value_at(&self, address: i32) -> Result<i32, IntcodeError> {
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress(address as i64));
+        }
+        return Ok(self.cell(address as usize));
+    }
+
+    // Memory past the end of the program starts out as zero
+    fn cell(&self, address: usize) -> i32 {
+        if let Some((mut device, offset)) = self.device_at(address) {
+            return device.read(offset);
+        }
+
+        return match self.values.get(address) {
+            None => 0,
+            Some(value) => *value
+        };
+    }
+
+    pub fn read(&mut self, position: i32, mode: ParameterMode) -> Result<i32, IntcodeError> {
+        return match mode {
+            ParameterMode::Immediate => self.value_at(position),
+            _ => {
+                let address = self.address(position, mode)?;
+                let value = self.value_at(address)?;
+                if self.observed {
+                    self.events.push(MemoryEvent::Read {address: address as usize, value});
+                }
+                Ok(value)
+            }
+        };
+    }
+
+    fn write_address(&mut self, position: i32, mode: ParameterMode) -> Result<i32, IntcodeError> {
+        return self.address(position, mode);
+    }
+
+    pub fn write(&mut self, position: i32, value: i32) -> Result<(), IntcodeError> {
+        if position < 0 {
+            return Err(IntcodeError::NegativeAddress(position as i64));
+        }
+        if self.observed {
+            // Devices aren't read to find the old value, since reading one can change it
+            let old = self.values.get(position as usize).cloned().unwrap_or(0);
+            self.events.push(MemoryEvent::Write {address: position as usize, old, new: value});
+        }
+        if let Some((mut device, offset)) = self.device_at(position as usize) {
+            device.write(offset, value);
+            return Ok(());
+        }
+        if position as usize >= self.values.len() {
+            self.values.resize(position as usize + 1, 0);
+        }
+        self.values[position as usize] = value;
+        return Ok(());
+    }
+
+    pub fn get_opcode(&self) -> i32 {
+        return self.cell(self.program_counter);
+    }
+}
+
+pub struct IntcodeComputer {
+    pub instructions: HashMap<u8, Instruction>,
+    pub profile: Profile,
+    trace: bool,
+    observers: Vec<SharedObserver>
+}
+
+impl IntcodeComputer {
+
+    pub fn new<T>(profile: Profile, instructions: T) -> IntcodeComputer
+    where T: IntoIterator<Item = Instruction>
+    {
+        let map: HashMap<u8, Instruction> = instructions.into_iter().map(|instruction| (instruction.opcode, instruction)).collect();
+        if map.contains_key(&HOST_CALL) && !profile.allows_host_calls() {
+            panic!("Host calls aren't part of the {} profile", profile.name());
+        }
+
+        return IntcodeComputer {
+            instructions: map,
+            profile,
+            trace: true,
+            observers: Vec::new()
+        };
+    }
+
+    pub fn with_profile(profile: Profile) -> IntcodeComputer {
+        return IntcodeComputer::new(profile, make_instructions(profile));
+    }
+
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    pub fn add_observer(&mut self, observer: SharedObserver) {
+        self.observers.push(observer);
+    }
+
+    // Asks every observer, going with the most drastic answer
+    fn notify<F>(&self, mut callback: F) -> Verdict
+    where F: FnMut(&mut dyn Observer) -> Verdict
+    {
+        let mut verdict = Verdict::Continue;
+        for observer in &self.observers {
+            let mut observer = match observer.lock() {
+                Err(why) => panic!("Observer is unusable: {}", why),
+                Ok(observer) => observer
+            };
+            verdict = verdict.max(callback(&mut *observer));
+        }
+        return verdict;
+    }
+
+    fn notify_events(&self, memory: &mut Memory, address: usize, step: usize) -> Verdict {
+        let mut verdict = Verdict::Continue;
+        for event in mem::take(&mut memory.events) {
+            verdict = verdict.max(self.notify(|observer| match event {
+                MemoryEvent::Read { address: cell, value } => observer.memory_read(address, cell, value),
+                MemoryEvent::Write { address: cell, old, new } => observer.memory_write(address, cell, old, new),
+                MemoryEvent::Input(value) => observer.input(step, value),
+                MemoryEvent::Output(value) => observer.output(step, value)
+            }));
+        }
+        return verdict;
+    }
+
+    // Runs the instruction at the pc, returning the state execution stopped in if it stopped
+    fn step(&self, memory: &mut Memory, input: &mut dyn Iterator<Item = &i32>, output: &mut Vec<i32>) -> Result<Option<ExecutionState>, IntcodeError> {
+        memory.observed = !self.observers.is_empty();
+
+        if memory.get_opcode() == 99 {
+            if !memory.halted {
+                memory.halted = true;
+                self.notify(|observer| {
+                    observer.halt(memory);
+                    return Verdict::Continue;
+                });
+            }
+            return Ok(Some(ExecutionState::Halted));
+        }
+        memory.halted = false;
+
+        let opcode = Instruction::decode_opcode(memory.get_opcode());
+        let instruction = match self.instructions.get(&opcode) {
+            None => return Err(IntcodeError::UnknownOpcode(opcode)),
+            Some(instruction) => instruction
+        };
+
+        let original_pc = memory.program_counter;
+        match self.notify(|observer| observer.before_instruction(memory, instruction)) {
+            Verdict::Abort => return Err(IntcodeError::Aborted(original_pc)),
+            Verdict::Pause => return Ok(Some(ExecutionState::Paused)),
+            Verdict::Continue => ()
+        };
+
+        match instruction.execute(memory, input, output, self.profile, self.trace) {
+            Err(IntcodeError::MissingInput) => {
+                memory.events.clear();
+                return Ok(Some(ExecutionState::AwaitingInput));
+            },
+            Err(why) => return Err(why),
+            Ok(()) => ()
+        };
+
+        let step = memory.steps;
+        memory.steps += 1;
+
+        // Only incrememnt if the instruction didn't modify the pc
+        if original_pc == memory.program_counter {
+            memory.program_counter += (instruction.parameter_count + 1) as usize;
+        }
+
+        if memory.observed {
+            let verdict = self.notify_events(memory, original_pc, step)
+                              .max(self.notify(|observer| observer.after_instruction(memory, original_pc, instruction)));
+            match verdict {
+                Verdict::Abort => return Err(IntcodeError::Aborted(original_pc)),
+                Verdict::Pause => return Ok(Some(ExecutionState::Paused)),
+                Verdict::Continue => ()
+            };
+        }
+        return Ok(None);
+    }
+
+    // Runs until the program halts, or until it needs more input than it has been given. Since
+    // an instruction that runs out of input doesn't change anything, calling this again with more
+    // input carries on from where it stopped. The same goes for observers pausing execution.
+    pub fn resume(&self, memory: &mut Memory, input: &mut dyn Iterator<Item = &i32>, output: &mut Vec<i32>) -> Result<ExecutionState, IntcodeError> {
+        loop {
+            if let Some(state) = self.step(memory, input, output)? {
+                return Ok(state);
+            }
+        }
+    }
+
+    // Like resume, but pauses once it has run limit instructions, so programs can take turns
+    pub fn resume_for(&self, memory: &mut Memory, input: &mut dyn Iterator<Item = &i32>, output: &mut Vec<i32>, limit: usize) -> Result<ExecutionState, IntcodeError> {
+        for _ in 0..limit {
+            if let Some(state) = self.step(memory, input, output)? {
+                return Ok(state);
+            }
+        }
+        return Ok(ExecutionState::Paused);
+    }
+
+    // Runs the program only as far as it needs to for each output asked for, so callers can stop
+    // early. Running out of input ends the outputs with a MissingInput error.
+    pub fn run_iter<'a, T>(&'a self, program: &Program, input: &'a T) -> Outputs<'a, <&'a T as IntoIterator>::IntoIter>
+    where &'a T: IntoIterator<Item = &'a i32>
+    {
+        return Outputs {computer: self, memory: Memory::initialize(program), input: input.into_iter(), output: VecDeque::new(), finished: false};
+    }
+
+    pub fn execute<T>(& self, program: & Program, input: &T, output: & mut Vec<i32>) -> Memory
+    where for<'a> &'a T: IntoIterator<Item = &'a i32>
+    {
+        let mut memory = Memory::initialize(program);
+        let mut input_iterator = input.into_iter();
+
+        return match self.resume(&mut memory, &mut input_iterator, output) {
+            Err(why) => panic!("{}", why),
+            Ok(ExecutionState::AwaitingInput) => panic!("{}", IntcodeError::MissingInput),
+            Ok(ExecutionState::Paused) => panic!("Paused at {}, which only resume can carry on from", memory.program_counter),
+            Ok(ExecutionState::Halted) => memory
+        };
+    }
+}
+
+pub struct Outputs<'a, I> {
+    computer: &'a IntcodeComputer,
+    memory: Memory,
+    input: I,
+    output: VecDeque<i32>,
+    finished: bool
+}
+
+impl<'a, I> Iterator for Outputs<'a, I> where I: Iterator<Item = &'a i32> {
+    type Item = Result<i32, IntcodeError>;
+
+    fn next(&mut self) -> Option<Result<i32, IntcodeError>> {
+        let mut output = Vec::new();
+        while self.output.is_empty() && !self.finished {
+            let state = self.computer.step(&mut self.memory, &mut self.input, &mut output);
+            self.output.extend(output.drain(..));
+            match state {
+                Err(why) => {
+                    self.finished = true;
+                    return Some(Err(why));
+                },
+                Ok(Some(ExecutionState::AwaitingInput)) => {
+                    self.finished = true;
+                    return Some(Err(IntcodeError::MissingInput));
+                },
+                Ok(Some(ExecutionState::Halted)) => self.finished = true,
+                // There is nobody to hand control to, so carry on
+                Ok(Some(ExecutionState::Paused)) | Ok(None) => ()
+            };
+        }
+        return self.output.pop_front().map(Ok);
+    }
+}
+
+pub fn make_instructions(profile: Profile) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+
+    // 1: p0 + p1 -> p2
+    instructions.push(Instruction::new_with_writes(1, 3, vec![2], |parameters, memory, _input, _output| {
+        memory.write(parameters[2], parameters[0] + parameters[1])?;
+        return Ok(());
+    }));
+
+    // 2: p0 * p1 -> p2
+    instructions.push(Instruction::new_with_writes(2, 3, vec![2], |parameters, memory, _input, _output| {
+        memory.write(parameters[2], parameters[0] * parameters[1])?;
+        return Ok(());
+    }));
+
+    if profile == Profile::Basic {
+        return instructions;
+    }
+
+    // 3: input -> p0
+    instructions.push(Instruction::new_with_writes(3, 1, vec![0], |parameters, memory, input, _output| {
+        let value = match input.next() {
+            None => return Err(IntcodeError::MissingInput),
+            Some(value) => value
+        };
+        memory.notify_input(*value);
+        memory.write(parameters[0], *value)?;
+        return Ok(());
+    }));
+
+    // 4: p0 -> output
+    instructions.push(Instruction::new(4, 1, |parameters, memory, _input, output| {
+        memory.notify_output(parameters[0]);
+        output.push(parameters[0]);
+        return Ok(());
+    }));
+
+    // 5: if p0 != 0, p1 -> PC
+    instructions.push(Instruction::new(5, 2, |parameters, memory, _input, _output| {
+        if parameters[0] != 0 {
+            memory.program_counter = parameters[1] as usize;
+        }
+        return Ok(());
+    }));
+
+    // 6: if p0 == 0, p1 -> PC
+    instructions.push(Instruction::new(6, 2, |parameters, memory, _input, _output| {
+        if parameters[0] == 0 {
+            memory.program_counter = parameters[1] as usize;
+        }
+        return Ok(());
+    }));
+
+    // 7: if p0 < p1, 1 -> p3 else 0 -> p3
+    instructions.push(Instruction::new_with_writes(7, 3, vec![2], |parameters, memory, _input, _output| {
+        if parameters[0] < parameters[1] {
+            memory.write(parameters[2], 1)?;
+        } else {
+            memory.write(parameters[2], 0)?;
+        }
+        return Ok(());
+    }));
+
+    // 8: if p0 == p1, 1 -> p3 else 0 -> p3
+    instructions.push(Instruction::new_with_writes(8, 3, vec![2], |parameters, memory, _input, _output| {
+        if parameters[0] == parameters[1] {
+            memory.write(parameters[2], 1)?;
+        } else {
+            memory.write(parameters[2], 0)?;
+        }
+        return Ok(());
+    }));
+
+    if profile == Profile::Diagnostic {
+        return instructions;
+    }
+
+    // 9: relative base + p0 -> relative base
+    instructions.push(Instruction::new(9, 1, |parameters, memory, _input, _output| {
+        memory.relative_base += parameters[0];
+        return Ok(());
+    }));
+
+    return instructions;
+}
+
+pub type HostFunction = Box<dyn Fn(&[i32]) -> Result<i32, String> + Send + Sync>;
+
+// Functions a program running on the hosted profile can call with:
+//
+//     10 index count destination
+//
+// which calls the function registered at `index` with the `count` cells starting at the relative
+// base as its arguments, and writes what it returns to `destination`.
+pub struct HostFunctions {
+    functions: Vec<(String, HostFunction)>
+}
+
+impl HostFunctions {
+    pub fn new() -> HostFunctions {
+        return HostFunctions {functions: Vec::new()};
+    }
+
+    // Returns the index programs use to call the function
+    pub fn register<F>(&mut self, name: &str, function: F) -> usize
+    where F: Fn(&[i32]) -> Result<i32, String> + Send + Sync + 'static
+    {
+        self.functions.push((name.to_string(), Box::new(function)));
+        return self.functions.len() - 1;
+    }
+
+    // The instruction to register with a computer so programs can call these functions
+    pub fn into_instruction(self) -> Instruction {
+        return Instruction::new_with_writes(HOST_CALL, 3, vec![2], move |parameters, memory, _input, _output| {
+            let (name, function) = match self.functions.get(parameters[0] as usize) {
+                None => return Err(IntcodeError::UnknownHostFunction(parameters[0])),
+                Some(entry) => entry
+            };
+
+            let frame = memory.relative_base..memory.relative_base + parameters[1];
+            let arguments = frame.map(|address| memory.value_at(address)).collect::<Result<Vec<i32>, IntcodeError>>()?;
+            let result = match function(&arguments) {
+                Err(message) => return Err(IntcodeError::HostFunctionFailed {name: name.clone(), message}),
+                Ok(result) => result
+            };
+
+            memory.write(parameters[2], result)?;
+            return Ok(());
+        });
+    }
+}
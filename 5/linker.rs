@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use super::Program;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelocationKind {
+    // A position mode operand, which can point anywhere, including past the end of the program
+    Position,
+    // An immediate jump target, which has to land inside the linked program
+    Jump
+}
+
+// A cell holding an address. What is in the cell is an offset from the symbol, or from the start
+// of the module if there is no symbol, and linking adds the address that ends up at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Relocation {
+    pub offset: usize,
+    pub kind: RelocationKind,
+    pub symbol: Option<String>
+}
+
+impl fmt::Display for Relocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            RelocationKind::Position => "position",
+            RelocationKind::Jump => "jump"
+        };
+        write!(f, "relocate {} {}", self.offset, kind)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " {}", symbol)?;
+        }
+        return Ok(());
+    }
+}
+
+// Code assembled as if it started at address 0, plus what's needed to move it somewhere else and
+// connect it to other modules
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectModule {
+    pub name: String,
+    pub code: Program,
+    // Symbol -> offset into the code. An export can be just past the end, to mark where free memory starts.
+    pub exports: BTreeMap<String, usize>,
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>
+}
+
+impl ObjectModule {
+    pub fn new(name: &str) -> ObjectModule {
+        return ObjectModule {name: name.to_string(), code: Vec::new(), exports: BTreeMap::new(), imports: Vec::new(), relocations: Vec::new()};
+    }
+
+    // Object files hold any number of modules, each a "module <name>" line followed by lines of
+    //
+    //     import <symbol>
+    //     export <symbol> <offset>
+    //     code <value>,<value>,...                  (appended to the code so far)
+    //     relocate <offset> position|jump [symbol]
+    //
+    // Lines starting with # are ignored.
+    pub fn parse(text: &str) -> Vec<ObjectModule> {
+        let mut modules: Vec<ObjectModule> = Vec::new();
+        for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with("#")) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if let ["module", name] = fields[..] {
+                modules.push(ObjectModule::new(name));
+                continue;
+            }
+
+            let module = match modules.last_mut() {
+                None => panic!("Object file line \"{}\" comes before any module", line),
+                Some(module) => module
+            };
+            match fields[..] {
+                ["import", symbol] => module.imports.push(symbol.to_string()),
+                ["export", symbol, offset] => {
+                    module.exports.insert(symbol.to_string(), parse_number(offset, line));
+                },
+                ["code", cells] => module.code.extend(cells.split(',').map(|cell| parse_number::<i32>(cell.trim(), line))),
+                ["relocate", offset, kind, ref symbol @ ..] if symbol.len() <= 1 => {
+                    let kind = match kind {
+                        "position" => RelocationKind::Position,
+                        "jump" => RelocationKind::Jump,
+                        _ => panic!("Unknown relocation kind \"{}\", expected position or jump", kind)
+                    };
+                    module.relocations.push(Relocation {offset: parse_number(offset, line), kind, symbol: symbol.first().map(|symbol| symbol.to_string())});
+                },
+                _ => panic!("Couldn't parse object file line \"{}\"", line)
+            };
+        }
+
+        return modules;
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, line: &str) -> T {
+    return match text.parse() {
+        Err(_) => panic!("Couldn't parse \"{}\" into a number in object file line \"{}\"", text, line),
+        Ok(number) => number
+    };
+}
+
+impl fmt::Display for ObjectModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "module {}", self.name)?;
+        for symbol in &self.imports {
+            writeln!(f, "import {}", symbol)?;
+        }
+        for (symbol, offset) in &self.exports {
+            writeln!(f, "export {} {}", symbol, offset)?;
+        }
+        let cells: Vec<String> = self.code.iter().map(|cell| cell.to_string()).collect();
+        writeln!(f, "code {}", cells.join(","))?;
+        for relocation in &self.relocations {
+            writeln!(f, "{}", relocation)?;
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkError {
+    DuplicateSymbol { symbol: String, modules: (String, String) },
+    // Imported, but no module exports it
+    UndefinedSymbol { module: String, symbol: String },
+    // Used by a relocation, but the module neither imports nor exports it
+    UndeclaredSymbol { module: String, symbol: String },
+    ExportOutOfRange { module: String, symbol: String, offset: usize },
+    RelocationOutOfRange { module: String, offset: usize },
+    JumpOutOfRange { module: String, offset: usize, target: i64 }
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkError::DuplicateSymbol { symbol, modules } => write!(f, "{} is exported by both {} and {}!", symbol, modules.0, modules.1),
+            LinkError::UndefinedSymbol { module, symbol } => write!(f, "{} imports {}, but nothing exports it!", module, symbol),
+            LinkError::UndeclaredSymbol { module, symbol } => write!(f, "{} relocates against {} without importing it!", module, symbol),
+            LinkError::ExportOutOfRange { module, symbol, offset } => write!(f, "{} exports {} at {}, past the end of its code!", module, symbol, offset),
+            LinkError::RelocationOutOfRange { module, offset } => write!(f, "{} has a relocation at {}, past the end of its code!", module, offset),
+            LinkError::JumpOutOfRange { module, offset, target } => write!(f, "The jump target at {} in {} becomes {}, outside the program!", offset, module, target)
+        }
+    }
+}
+
+pub struct Linked {
+    pub program: Program,
+    // Every exported symbol at its final address, in the same form as symbol files
+    pub symbols: Vec<(usize, String)>,
+    // Where each module ended up
+    pub layout: Vec<(String, Range<usize>)>
+}
+
+// Lays the modules out one after another in the order given, so the first one is where the program
+// starts, then patches every relocated cell with the address it refers to
+pub fn link(modules: &[ObjectModule]) -> Result<Linked, LinkError> {
+    let mut layout = Vec::new();
+    let mut base = 0;
+    for module in modules {
+        layout.push((module.name.clone(), base..base + module.code.len()));
+        base += module.code.len();
+    }
+
+    let mut addresses: BTreeMap<&str, (usize, &str)> = BTreeMap::new();
+    for (module, (_, range)) in modules.iter().zip(&layout) {
+        for (symbol, &offset) in &module.exports {
+            if offset > module.code.len() {
+                return Err(LinkError::ExportOutOfRange {module: module.name.clone(), symbol: symbol.clone(), offset});
+            }
+            if let Some((_, other)) = addresses.insert(symbol, (range.start + offset, &module.name)) {
+                return Err(LinkError::DuplicateSymbol {symbol: symbol.clone(), modules: (other.to_string(), module.name.clone())});
+            }
+        }
+    }
+
+    let mut program: Program = modules.iter().flat_map(|module| module.code.iter().cloned()).collect();
+    for (module, (_, range)) in modules.iter().zip(&layout) {
+        for symbol in &module.imports {
+            if !addresses.contains_key(symbol.as_str()) {
+                return Err(LinkError::UndefinedSymbol {module: module.name.clone(), symbol: symbol.clone()});
+            }
+        }
+
+        for relocation in &module.relocations {
+            if relocation.offset >= module.code.len() {
+                return Err(LinkError::RelocationOutOfRange {module: module.name.clone(), offset: relocation.offset});
+            }
+
+            let address = match &relocation.symbol {
+                None => range.start,
+                Some(symbol) if module.imports.contains(symbol) || module.exports.contains_key(symbol) => addresses[symbol.as_str()].0,
+                Some(symbol) => return Err(LinkError::UndeclaredSymbol {module: module.name.clone(), symbol: symbol.clone()})
+            };
+
+            let cell = range.start + relocation.offset;
+            let target = program[cell] as i64 + address as i64;
+            if relocation.kind == RelocationKind::Jump && (target < 0 || target >= program.len() as i64) {
+                return Err(LinkError::JumpOutOfRange {module: module.name.clone(), offset: relocation.offset, target});
+            }
+            program[cell] = target as i32;
+        }
+    }
+
+    let mut symbols: Vec<(usize, String)> = addresses.iter().map(|(symbol, &(address, _))| (address, symbol.to_string())).collect();
+    symbols.sort();
+    return Ok(Linked {program, symbols, layout});
+}
@@ -1,212 +1,974 @@
-use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod analysis;
+mod ascii;
+mod binary;
+mod compiler;
+mod coverage;
+mod decompile;
+mod devices;
+mod diagnostic;
+mod disassembly;
+mod fuzz;
+mod generator;
+mod grid;
+mod hexdump;
+mod intcode;
+mod linker;
+mod maze;
+mod minimise;
+mod optimiser;
+mod replay;
+mod robot;
+mod scheduler;
+mod screen;
+mod self_modifying;
+mod threaded;
+mod transpile;
+mod watch;
+
+use intcode::{ExecutionState, HostFunctions, IntcodeComputer, IntcodeError, Memory, Profile, Program};
 
-#[derive(Clone, Copy, Debug)]
-enum ParameterMode {
-    Immediate,
-    Position
+fn read_program() -> Vec<i32> {
+    return vec![3,225,1,225,6,6,1100,1,238,225,104,0,1002,43,69,224,101,-483,224,224,4,224,1002,223,8,223,1001,224,5,224,1,224,223,223,1101,67,60,225,1102,5,59,225,1101,7,16,225,1102,49,72,225,101,93,39,224,101,-98,224,224,4,224,102,8,223,223,1001,224,6,224,1,224,223,223,1102,35,82,225,2,166,36,224,101,-4260,224,224,4,224,102,8,223,223,101,5,224,224,1,223,224,223,102,66,48,224,1001,224,-4752,224,4,224,102,8,223,223,1001,224,2,224,1,223,224,223,1001,73,20,224,1001,224,-55,224,4,224,102,8,223,223,101,7,224,224,1,223,224,223,1102,18,41,224,1001,224,-738,224,4,224,102,8,223,223,101,6,224,224,1,224,223,223,1101,68,71,225,1102,5,66,225,1101,27,5,225,1101,54,63,224,1001,224,-117,224,4,224,102,8,223,223,1001,224,2,224,1,223,224,223,1,170,174,224,101,-71,224,224,4,224,1002,223,8,223,1001,224,4,224,1,223,224,223,4,223,99,0,0,0,677,0,0,0,0,0,0,0,0,0,0,0,1105,0,99999,1105,227,247,1105,1,99999,1005,227,99999,1005,0,256,1105,1,99999,1106,227,99999,1106,0,265,1105,1,99999,1006,0,99999,1006,227,274,1105,1,99999,1105,1,280,1105,1,99999,1,225,225,225,1101,294,0,0,105,1,0,1105,1,99999,1106,0,300,1105,1,99999,1,225,225,225,1101,314,0,0,106,0,0,1105,1,99999,1007,226,226,224,1002,223,2,223,1006,224,329,1001,223,1,223,1007,226,677,224,102,2,223,223,1006,224,344,1001,223,1,223,108,677,677,224,102,2,223,223,1005,224,359,1001,223,1,223,1007,677,677,224,1002,223,2,223,1006,224,374,101,1,223,223,8,677,226,224,1002,223,2,223,1006,224,389,101,1,223,223,7,226,226,224,1002,223,2,223,1005,224,404,101,1,223,223,7,677,226,224,102,2,223,223,1005,224,419,1001,223,1,223,8,226,677,224,1002,223,2,223,1005,224,434,101,1,223,223,1008,226,677,224,102,2,223,223,1006,224,449,1001,223,1,223,7,226,677,224,1002,223,2,223,1006,224,464,1001,223,1,223,108,677,226,224,102,2,223,223,1005,224,479,101,1,223,223,108,226,226,224,1002,223,2,223,1006,224,494,101,1,223,223,8,226,226,224,1002,223,2,223,1005,224,509,1001,223,1,223,1107,677,226,224,102,2,223,223,1005,224,524,1001,223,1,223,1107,226,226,224,102,2,223,223,1005,224,539,1001,223,1,223,1108,677,677,224,1002,223,2,223,1006,224,554,101,1,223,223,107,226,677,224,102,2,223,223,1005,224,569,1001,223,1,223,1108,226,677,224,1002,223,2,223,1005,224,584,1001,223,1,223,1107,226,677,224,1002,223,2,223,1005,224,599,1001,223,1,223,1008,226,226,224,1002,223,2,223,1005,224,614,101,1,223,223,107,226,226,224,102,2,223,223,1006,224,629,1001,223,1,223,1008,677,677,224,1002,223,2,223,1006,224,644,101,1,223,223,107,677,677,224,1002,223,2,223,1005,224,659,101,1,223,223,1108,677,226,224,1002,223,2,223,1006,224,674,1001,223,1,223,4,223,99,226];
 }
 
-type InstructionHandler = fn(Vec<i32>, &mut Memory, &mut dyn Iterator<Item = &i32>, &mut Vec<i32>);
-
-struct Instruction {
-    opcode: u8,
-    parameter_count: u8,
-    write_parameters: Vec<u8>,
-    handler: InstructionHandler
+fn parse_program(text: &str) -> Program {
+    return text.trim()
+               .split(",")
+               .map(|value| match value.trim().parse() {
+                   Err(why) => panic!("Couldn't parse \"{}\" into a number: {}", value, why),
+                   Ok(value) => value
+               })
+               .collect();
 }
 
-impl Instruction {
+// Reads either the comma separated text form or the binary form of a program
+fn read_image(file_name: &Path) -> binary::Image {
+    let bytes = match fs::read(file_name) {
+        Err(why) => panic!("Couldn't open {}: {}", file_name.display(), why),
+        Ok(bytes) => bytes
+    };
+
+    if binary::is_binary(&bytes) {
+        return match binary::Image::decode(&bytes) {
+            Err(why) => panic!("Couldn't read {}: {}", file_name.display(), why),
+            Ok(image) => image
+        };
+    }
+
+    return match String::from_utf8(bytes) {
+        Err(why) => panic!("Couldn't read {}: {}", file_name.display(), why),
+        Ok(text) => binary::Image::program(Profile::Diagnostic, parse_program(&text))
+    };
+}
 
-    fn new_with_writes(opcode: u8, parameter_count: u8, write_parameters: Vec<u8>, handler: InstructionHandler) -> Instruction {
-        return Instruction{opcode, parameter_count, write_parameters, handler};
+fn read_program_file(file_name: &Path) -> Program {
+    let image = read_image(file_name);
+    if image.kind == binary::Kind::Snapshot {
+        panic!("{} is a snapshot of a running program, which only the run command can resume", file_name.display());
     }
+    return image.cells;
+}
+
+fn format_program(program: &Program) -> String {
+    return program.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",");
+}
 
-    fn new(opcode: u8, parameter_count: u8, handler: InstructionHandler) -> Instruction {
-        return Instruction::new_with_writes(opcode, parameter_count, Vec::new(), handler);
+fn write_file(file_name: &Path, contents: &str) {
+    if let Err(why) = fs::write(file_name, contents) {
+        panic!("Couldn't write {}: {}", file_name.display(), why);
     }
+}
 
-    fn decode_parameter_mode(value: i32, parameter_position: u8) -> ParameterMode {
-        let mut mode = value;
+fn parse_inputs(values: &[String]) -> Vec<i32> {
+    return values.iter()
+                 .map(|value| match value.parse() {
+                     Err(why) => panic!("Couldn't parse input \"{}\" into a number: {}", value, why),
+                     Ok(value) => value
+                 })
+                 .collect();
+}
 
-        // We need to get rid of the first two digits as well, so we divide 2 extra times
-        for _ in 0..parameter_position + 2 {
-            mode /= 10;
+// screen <program file> [--headless] [--glyph=<tile>:<char>]... [inputs]...
+fn run_screen(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+
+    let mut headless = false;
+    let mut screen = screen::Screen::new((-1, 0));
+    let mut input_arguments = Vec::new();
+    for argument in &args[1..] {
+        if argument == "--headless" {
+            headless = true;
+        } else if let Some(mapping) = argument.strip_prefix("--glyph=") {
+            let mapping: Vec<&str> = mapping.splitn(2, ":").collect();
+            match (mapping[0].parse(), mapping.get(1).and_then(|glyph| glyph.chars().next())) {
+                (Ok(tile), Some(glyph)) => screen.set_glyph(tile, glyph),
+                _ => panic!("Couldn't parse glyph mapping \"{}\"", argument)
+            };
+        } else {
+            input_arguments.push(argument.clone());
         }
+    }
 
-        let mode_value = match mode % 10 {
-            0 => ParameterMode::Position,
-            1 => ParameterMode::Immediate,
-            _ => panic!("Unknown parameter mode: {}!", mode)
+    let mut computer = IntcodeComputer::with_profile(Profile::Diagnostic);
+    computer.set_trace(false);
+    let input = parse_inputs(&input_arguments);
+    let mut output = Vec::new();
+    for value in computer.run_iter(&program, &input) {
+        match value {
+            Err(why) => panic!("Screen program failed: {}", why),
+            Ok(value) => output.push(value)
         };
 
-        return mode_value;
+        // Redraw as each triple arrives, but only if it changed something
+        if !headless && screen.update(&output) {
+            if let Err(why) = screen.draw(&mut io::stdout()) {
+                panic!("Couldn't draw to the terminal: {}", why);
+            }
+        }
+    }
+
+    if headless {
+        print!("{}", screen.render_headless(&output));
+        return;
     }
 
-    fn decode_opcode(value: i32) -> u8 {
-        return (value % 100) as u8;
+    println!("Block tiles: {}", screen.count_tiles(2));
+    if let Some(score) = screen.score() {
+        println!("Final score: {}", score);
     }
+}
 
-    fn execute(&self, memory: &mut Memory, input: &mut dyn Iterator<Item = &i32>, output: &mut Vec<i32>) {
-        let mut parameters = Vec::new();
-        let opcode = memory.get_opcode();
-        for i in 0..self.parameter_count {
-            let parameter_mode = if self.write_parameters.contains(&i) {
-                                    ParameterMode::Immediate
-                                } else {
-                                    Instruction::decode_parameter_mode(opcode, i)
-                                };
-            let address = (memory.program_counter as i32) + (i as i32) + 1 as i32;
-            let value = memory.read(address, parameter_mode);
+// robot <program file> [--record=<replay file>] [starting colour]
+fn run_robot(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let (replay_file, arguments) = option_argument(&args[1..], "--record=");
+    let starting_colour = match arguments.first() {
+        None => robot::BLACK,
+        Some(colour) => parse_inputs(std::slice::from_ref(colour))[0]
+    };
+
+    let mut computer = IntcodeComputer::with_profile(Profile::Diagnostic);
+    computer.set_trace(false);
+    let recording = start_recording(&mut computer, replay_file);
+
+    let mut memory = Memory::initialize(&program);
+    let mut robot = robot::Robot::new(starting_colour);
+    if let Err(why) = robot.run(&computer, &mut memory) {
+        panic!("Robot program failed: {}", why);
+    }
+    save_recording(recording);
 
-            parameters.push(value);
-        }
+    print!("{}", robot.render());
+    println!("Painted {} cells at least once", robot.painted_count());
+}
 
-        println!("Executing {} {:?}", opcode, parameters);
+// maze <program file>
+// There's no --record, since exploring resumes copies of the droid from every square it reaches, so
+// there is no single run to replay.
+fn run_maze(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+
+    let mut computer = IntcodeComputer::with_profile(Profile::Diagnostic);
+    computer.set_trace(false);
+
+    let maze = match maze::Maze::explore(&computer, &program) {
+        Err(why) => panic!("Droid program failed: {}", why),
+        Ok(maze) => maze
+    };
+
+    print!("{}", maze.render());
+    match (maze.target(), maze.path_to_target()) {
+        (Some(target), Some(path)) => {
+            println!("Shortest path to the target at {} is {} moves: {:?}", target, path.len(), path);
+            println!("Flooding the maze from the target takes {} steps", maze.flood_fill_distance(target));
+        },
+        _ => println!("The target wasn't found")
+    };
+}
 
-        (self.handler)(parameters, memory, input, output);
+// Splits out an argument like --record=<replay file>, if there is one
+fn option_argument(args: &[String], option: &str) -> (Option<String>, Vec<String>) {
+    let mut value = None;
+    let mut rest = Vec::new();
+    for argument in args {
+        if let Some(option_value) = argument.strip_prefix(option) {
+            value = Some(option_value.to_string());
+        } else {
+            rest.push(argument.clone());
+        }
     }
+    return (value, rest);
 }
 
-type Program = Vec<i32>;
-struct Memory {
-    program_counter: usize,
-    values: Vec<i32>
+fn parse_profile(name: &str) -> Profile {
+    return match Profile::parse(name) {
+        None => panic!("Unknown instruction set profile \"{}\", expected basic, diagnostic, extended or hosted", name),
+        Some(profile) => profile
+    };
 }
 
-impl Memory {
-    fn initialize(program: &Program) -> Memory {
-        return Memory {
-            program_counter: 0,
-            values: program.clone()
+// Records the computer's input and output if a replay file was asked for
+fn start_recording(computer: &mut IntcodeComputer, replay_file: Option<String>) -> Option<(Arc<Mutex<replay::Recording>>, String)> {
+    let replay_file = replay_file?;
+    let recording = Arc::new(Mutex::new(replay::Recording::new(computer.profile)));
+    computer.add_observer(recording.clone());
+    return Some((recording, replay_file));
+}
+
+fn save_recording(recording: Option<(Arc<Mutex<replay::Recording>>, String)>) {
+    if let Some((recording, replay_file)) = recording {
+        match recording.lock() {
+            Err(why) => panic!("Recording is unusable: {}", why),
+            Ok(recording) => write_file(Path::new(&replay_file), &recording.to_string())
         };
     }
+}
+
+// What programs on the hosted profile can call from the command line
+fn host_functions() -> HostFunctions {
+    let mut functions = HostFunctions::new();
+    functions.register("print", |arguments| {
+        println!("{}", format_program(&arguments.to_vec()));
+        return Ok(arguments.len() as i32);
+    });
+    functions.register("sum", |arguments| {
+        return arguments.iter().try_fold(0i32, |total, &value| total.checked_add(value)).ok_or(String::from("overflow"));
+    });
+    functions.register("max", |arguments| {
+        return arguments.iter().cloned().max().ok_or(String::from("no arguments"));
+    });
+    return functions;
+}
+
+// A computer for the profile, which on the hosted profile can call host_functions
+fn make_computer(profile: Profile) -> IntcodeComputer {
+    let mut instructions = intcode::make_instructions(profile);
+    if profile.allows_host_calls() {
+        instructions.push(host_functions().into_instruction());
+    }
+
+    let mut computer = IntcodeComputer::new(profile, instructions);
+    computer.set_trace(false);
+    return computer;
+}
+
+fn parse_address(text: &str) -> usize {
+    return match text.parse() {
+        Err(why) => panic!("Couldn't parse \"{}\" into an address: {}", text, why),
+        Ok(address) => address
+    };
+}
 
-    fn read(&self, position: i32, mode: ParameterMode) -> i32 {
-        return match mode {
-            ParameterMode::Immediate => self.values[position as usize],
-            ParameterMode::Position => {
-                let address = self.values[position as usize];
-                self.values[address as usize]
+// Attaches whatever devices were asked for with --clock=<address>, --random=<address>[:<seed>]
+// and --framebuffer=<address>:<width>x<height>. The framebuffers are returned so they can be shown.
+fn attach_devices(memory: &mut Memory, args: &[String]) -> (Vec<Arc<Mutex<devices::Framebuffer>>>, Vec<String>) {
+    let mut framebuffers = Vec::new();
+    let mut rest = Vec::new();
+    for argument in args {
+        if let Some(address) = argument.strip_prefix("--clock=") {
+            let address = parse_address(address);
+            memory.attach_device(address..address + 1, Arc::new(Mutex::new(devices::Clock::new())));
+        } else if let Some(device) = argument.strip_prefix("--random=") {
+            let fields: Vec<&str> = device.splitn(2, ":").collect();
+            let address = parse_address(fields[0]);
+            let seed = fields.get(1).map(|seed| parse_inputs(&[seed.to_string()])[0]).unwrap_or(1);
+            memory.attach_device(address..address + 1, Arc::new(Mutex::new(devices::Random::new(seed))));
+        } else if let Some(device) = argument.strip_prefix("--framebuffer=") {
+            let fields: Vec<&str> = device.split([':', 'x']).collect();
+            if fields.len() != 3 {
+                panic!("Couldn't parse framebuffer \"{}\", expected <address>:<width>x<height>", argument);
             }
+            let address = parse_address(fields[0]);
+            let framebuffer = devices::Framebuffer::new(parse_address(fields[1]), parse_address(fields[2]));
+            let size = framebuffer.size();
+            let framebuffer = Arc::new(Mutex::new(framebuffer));
+            memory.attach_device(address..address + size, framebuffer.clone());
+            framebuffers.push(framebuffer);
+        } else {
+            rest.push(argument.clone());
+        }
+    }
+    return (framebuffers, rest);
+}
+
+// run <program or snapshot file> [--profile=<basic|diagnostic|extended|hosted>] [--record=<replay file>]
+//     [--watch=<address>] [--max-steps=<n>] [--self-modifying=<warn|trap>] [devices] [inputs]...
+// Binary files say which profile they need, and anything else is taken to be a Day 5 program. See
+// attach_devices for the devices that can be mapped into memory. --watch prints every write to an
+// address, --max-steps gives up on programs that run too long, and --self-modifying reports (or stops
+// at) instructions that write over code.
+fn run_program(args: &[String]) {
+    let image = read_image(Path::new(&args[0]));
+    let (profile, arguments) = option_argument(&args[1..], "--profile=");
+    let (replay_file, arguments) = option_argument(&arguments, "--record=");
+    let (watched, arguments) = option_argument(&arguments, "--watch=");
+    let (step_limit, arguments) = option_argument(&arguments, "--max-steps=");
+    let (policy, arguments) = option_argument(&arguments, "--self-modifying=");
+    let profile = profile.map(|name| parse_profile(&name)).unwrap_or(image.profile);
+
+    let mut computer = make_computer(profile);
+    let recording = start_recording(&mut computer, replay_file);
+    let watchpoint = watched.map(|address| Arc::new(Mutex::new(watch::Watchpoint::new(parse_address(&address)))));
+    if let Some(watchpoint) = &watchpoint {
+        computer.add_observer(watchpoint.clone());
+    }
+    if let Some(step_limit) = step_limit {
+        computer.add_observer(Arc::new(Mutex::new(watch::StepLimit::new(parse_address(&step_limit)))));
+    }
+    let detector = policy.map(|name| match self_modifying::Policy::parse(&name) {
+        None => panic!("Unknown self modifying code policy \"{}\", expected warn or trap", name),
+        Some(policy) => Arc::new(Mutex::new(self_modifying::SelfModificationDetector::new(policy)))
+    });
+    if let Some(detector) = &detector {
+        computer.add_observer(detector.clone());
+    }
+
+    let mut memory = Memory::initialize(&image.cells);
+    memory.program_counter = image.program_counter;
+    memory.relative_base = image.relative_base;
+    let (framebuffers, input_arguments) = attach_devices(&mut memory, &arguments);
+
+    // Anything on the command line comes after the input stored with the program
+    let mut input = image.input.clone().unwrap_or_default();
+    input.extend(parse_inputs(&input_arguments));
+    let mut output = Vec::new();
+    let mut input = input.iter();
+
+    // Save what happened even if the program fails, since that is when the recording is most useful
+    let result = loop {
+        match computer.resume(&mut memory, &mut input, &mut output) {
+            Ok(ExecutionState::Paused) => (),
+            result => break result
         };
+
+        // Only the watchpoint pauses
+        if let Some(watchpoint) = &watchpoint {
+            if let Ok(watchpoint) = watchpoint.lock() {
+                let (pc, old, new) = watchpoint.writes.last().unwrap();
+                println!("Step {}: instruction at {} changed {} to {}", memory.steps, pc, old, new);
+            }
+        }
+    };
+    save_recording(recording);
+    if let Some(detector) = &detector {
+        if let Ok(detector) = detector.lock() {
+            for patch in &detector.patches {
+                println!("{}", patch);
+            }
+        }
     }
+    match result {
+        Err(why) => panic!("{}", why),
+        Ok(ExecutionState::AwaitingInput) => panic!("{}", IntcodeError::MissingInput),
+        Ok(_) => ()
+    };
+
+    for framebuffer in framebuffers {
+        match framebuffer.lock() {
+            Err(why) => panic!("Framebuffer is unusable: {}", why),
+            Ok(framebuffer) => print!("{}", framebuffer.render())
+        };
+    }
+    println!("Output:\n{:?}", output);
+}
 
-    fn write(&mut self, position: i32, value: i32) {
-        self.values[position as usize] = value;
+// replay <program file> <replay file> [--profile=<name>]
+// The run is repeated with the profile the recording was made with, unless --profile says otherwise
+fn run_replay(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let (profile, arguments) = option_argument(&args[1..], "--profile=");
+    let replay_file = match arguments.first() {
+        None => panic!("Usage: replay <program file> <replay file> [--profile=<name>]"),
+        Some(replay_file) => Path::new(replay_file)
+    };
+    let expected = match fs::read_to_string(replay_file) {
+        Err(why) => panic!("Couldn't open {}: {}", replay_file.display(), why),
+        Ok(text) => replay::Recording::parse(&text)
+    };
+    let profile = profile.map(|name| parse_profile(&name)).unwrap_or(expected.profile);
+
+    let mut computer = make_computer(profile);
+    let recording = Arc::new(Mutex::new(replay::Recording::new(profile)));
+    computer.add_observer(recording.clone());
+
+    let input = expected.inputs();
+    let mut output = Vec::new();
+    let mut memory = Memory::initialize(&program);
+    let state = computer.resume(&mut memory, &mut input.iter(), &mut output);
+
+    let replayed = match recording.lock() {
+        Err(why) => panic!("Recording is unusable: {}", why),
+        Ok(replayed) => replayed.clone()
+    };
+    match expected.divergence(&replayed) {
+        Some(divergence) => panic!("Replay failed after {} instructions ({:?}). {}", memory.steps, state, divergence),
+        None => println!("Replayed {} events over {} instructions", expected.events.len(), memory.steps)
+    };
+}
+
+// coverage <program file> [--listing=<file>] [--lcov=<file>] [inputs]...
+fn run_coverage(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+
+    let mut listing_file = None;
+    let mut lcov_file = None;
+    let mut input_arguments = Vec::new();
+    for argument in &args[1..] {
+        if let Some(file) = argument.strip_prefix("--listing=") {
+            listing_file = Some(file.to_string());
+        } else if let Some(file) = argument.strip_prefix("--lcov=") {
+            lcov_file = Some(file.to_string());
+        } else {
+            input_arguments.push(argument.clone());
+        }
     }
 
-    fn get_opcode(&self) -> i32 {
-        return self.values[self.program_counter];
+    let mut computer = IntcodeComputer::with_profile(Profile::Diagnostic);
+    computer.set_trace(false);
+    let coverage = Arc::new(Mutex::new(coverage::Coverage::new()));
+    computer.add_observer(coverage.clone());
+
+    let input = parse_inputs(&input_arguments);
+    let mut output = Vec::new();
+    computer.execute(&program, &input, &mut output);
+
+    let coverage = match coverage.lock() {
+        Err(why) => panic!("Coverage is unusable: {}", why),
+        Ok(coverage) => coverage.clone()
+    };
+    let listing = coverage.annotated_listing(&computer, &program);
+    match listing_file {
+        None => print!("{}", listing),
+        Some(ref listing_file) => write_file(Path::new(listing_file), &listing)
+    };
+    if let Some(lcov_file) = lcov_file {
+        let source = listing_file.unwrap_or_else(|| format!("{}.listing", args[0]));
+        write_file(Path::new(&lcov_file), &coverage.lcov(&computer, &program, &source));
     }
+    println!("Output:\n{:?}", output);
 }
 
-struct IntcodeComputer {
-    instructions: HashMap<u8, Instruction>
+// pack <program file> <binary file> [--profile=<name>] [--symbols=<file>] [--input=<value>,...] [--snapshot]
+// With --snapshot, the program is run on the input until it halts or wants more, and the result saved
+fn run_pack(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let binary_file = match args.get(1) {
+        None => panic!("Usage: pack <program file> <binary file> [--profile=<name>] [--symbols=<file>] [--input=<value>,...] [--snapshot]"),
+        Some(binary_file) => Path::new(binary_file)
+    };
+
+    let mut image = binary::Image::program(Profile::Diagnostic, program);
+    let mut snapshot = false;
+    for argument in &args[2..] {
+        if let Some(symbol_file) = argument.strip_prefix("--symbols=") {
+            let symbol_file = Path::new(symbol_file);
+            image.symbols = match fs::read_to_string(symbol_file) {
+                Err(why) => panic!("Couldn't open {}: {}", symbol_file.display(), why),
+                Ok(text) => binary::parse_symbols(&text)
+            };
+        } else if let Some(name) = argument.strip_prefix("--profile=") {
+            image.profile = parse_profile(name);
+        } else if let Some(input) = argument.strip_prefix("--input=") {
+            image.input = Some(parse_program(input));
+        } else if argument == "--snapshot" {
+            snapshot = true;
+        } else {
+            panic!("Unknown pack option: {}", argument);
+        }
+    }
+
+    if snapshot {
+        let computer = make_computer(image.profile);
+
+        let input = image.input.take().unwrap_or_default();
+        let mut output = Vec::new();
+        let mut memory = Memory::initialize(&image.cells);
+        let state = match computer.resume(&mut memory, &mut input.iter(), &mut output) {
+            Err(why) => panic!("{}", why),
+            Ok(state) => state
+        };
+        println!("Snapshot taken when {:?} after {} instructions, with output {:?}", state, memory.steps, output);
+
+        let symbols = image.symbols;
+        image = binary::Image::snapshot(image.profile, memory.program_counter, memory.relative_base, memory.values);
+        image.symbols = symbols;
+    }
+
+    let bytes = image.encode();
+    if let Err(why) = fs::write(binary_file, &bytes) {
+        panic!("Couldn't write {}: {}", binary_file.display(), why);
+    }
+    println!("Packed {} cells into {} bytes", image.cells.len(), bytes.len());
+}
+
+// unpack <binary file> [program file]
+fn run_unpack(args: &[String]) {
+    let image = read_image(Path::new(&args[0]));
+
+    println!("{:?} for the {} profile, {} cells", image.kind, image.profile.name(), image.cells.len());
+    if image.kind == binary::Kind::Snapshot {
+        println!("Program counter: {}", image.program_counter);
+        println!("Relative base: {}", image.relative_base);
+    }
+    for (address, name) in &image.symbols {
+        println!("Symbol {}: {}", address, name);
+    }
+    if let Some(input) = &image.input {
+        println!("Input: {}", format_program(input));
+    }
+
+    match args.get(1) {
+        None => println!("{}", format_program(&image.cells)),
+        Some(program_file) => write_file(Path::new(program_file), &format_program(&image.cells))
+    };
+}
+
+// analyse <program file> [--profile=<name>] [inputs]...
+// Works out what the program can do without running it. Input instructions read one of the given
+// inputs, or anything if there are none.
+fn run_analyse(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let (profile, input_arguments) = option_argument(&args[1..], "--profile=");
+    let computer = make_computer(profile.map(|name| parse_profile(&name)).unwrap_or(Profile::Diagnostic));
+    let inputs = parse_inputs(&input_arguments);
+
+    let analysis = analysis::analyse(&computer, &program, if inputs.is_empty() { None } else { Some(&inputs) });
+    print!("{}", analysis);
+    if !analysis.complete {
+        return;
+    }
+
+    let never_written = (0..program.len()).filter(|address| !analysis.written.contains_key(address)).count();
+    println!("{} of {} cells are never written", never_written, program.len());
+    let control_flow = disassembly::trace_control_flow(&computer, &program);
+    for address in analysis.unreachable(&control_flow) {
+        println!("{:>5}: unreachable {}", address, control_flow.instructions[&address]);
+    }
+}
+
+// compile <source file> [--output=<program file>] [--object=<object file>] [--run] [inputs]...
+// Compiles a program in the little language described in compiler.rs. With --run it is run on the
+// inputs instead of being written out.
+fn run_compile(args: &[String]) {
+    let source_file = Path::new(&args[0]);
+    let (output_file, arguments) = option_argument(&args[1..], "--output=");
+    let (object_file, arguments) = option_argument(&arguments, "--object=");
+    let run = arguments.iter().any(|argument| argument == "--run");
+    let input_arguments: Vec<String> = arguments.into_iter().filter(|argument| argument != "--run").collect();
+
+    let source = match fs::read_to_string(source_file) {
+        Err(why) => panic!("Couldn't open {}: {}", source_file.display(), why),
+        Ok(source) => source
+    };
+    let name = source_file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or(String::from("main"));
+    let module = match compiler::compile(&source, &name) {
+        Err(why) => panic!("Couldn't compile {}: {}", source_file.display(), why),
+        Ok(module) => module
+    };
+
+    if let Some(object_file) = object_file {
+        write_file(Path::new(&object_file), &module.to_string());
+    }
+    let program = match linker::link(&[module]) {
+        Err(why) => panic!("{}", why),
+        Ok(linked) => linked.program
+    };
+
+    if run {
+        let computer = make_computer(Profile::Diagnostic);
+        let mut output = Vec::new();
+        computer.execute(&program, &parse_inputs(&input_arguments), &mut output);
+        println!("Output:\n{:?}", output);
+        return;
+    }
+    match output_file {
+        None => println!("{}", format_program(&program)),
+        Some(output_file) => write_file(Path::new(&output_file), &format_program(&program))
+    };
+}
+
+// decompile <program file> [inputs]...
+fn run_decompile(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let inputs = parse_inputs(&args[1..]);
+
+    let computer = IntcodeComputer::with_profile(Profile::Diagnostic);
+    print!("{}", decompile::decompile(&computer, &program, if inputs.is_empty() { None } else { Some(&inputs) }));
 }
 
-impl IntcodeComputer {
-    
-    fn new<T>(instructions: T) -> IntcodeComputer 
-    where T: IntoIterator<Item = Instruction> 
-    {
-        let map = instructions.into_iter().map(|instruction| (instruction.opcode, instruction)).collect();
-        return IntcodeComputer {
-            instructions: map
+// optimise <program file> [output file]
+fn run_optimise(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+
+    let computer = IntcodeComputer::with_profile(Profile::Diagnostic);
+    let (optimised, report) = optimiser::optimise(&computer, &program);
+
+    print!("{}", report);
+    match args.get(1) {
+        None => println!("{}", format_program(&optimised)),
+        Some(output_file) => write_file(Path::new(output_file), &format_program(&optimised))
+    };
+}
+
+// link <object file>... [--output=<program file>] [--symbols=<symbol file>]
+// Links every module in the object files into one program, laid out in the order given
+fn run_link(args: &[String]) {
+    let (output_file, arguments) = option_argument(args, "--output=");
+    let (symbol_file, object_files) = option_argument(&arguments, "--symbols=");
+
+    let mut modules = Vec::new();
+    for object_file in object_files.iter().map(Path::new) {
+        match fs::read_to_string(object_file) {
+            Err(why) => panic!("Couldn't open {}: {}", object_file.display(), why),
+            Ok(text) => modules.extend(linker::ObjectModule::parse(&text))
         };
     }
 
-    fn execute<T>(& self, program: & Program, input: &T, output: & mut Vec<i32>) -> Memory
-    where for<'a> &'a T: IntoIterator<Item = &'a i32>
-    {
-        let mut memory = Memory::initialize(&program);
-        let mut input_iterator = input.into_iter();
+    let linked = match linker::link(&modules) {
+        Err(why) => panic!("{}", why),
+        Ok(linked) => linked
+    };
 
-        while memory.get_opcode() != 99 {
-            let opcode = Instruction::decode_opcode(memory.get_opcode());
-            let instruction = match self.instructions.get(&opcode) {
-                None => panic!("Missing instruction definition for opcode {}!", opcode),
-                Some(instruction) => instruction
-            };
+    for (name, range) in &linked.layout {
+        println!("{:>5}..{:<5} {}", range.start, range.end, name);
+    }
+    if let Some(symbol_file) = symbol_file {
+        let symbols: String = linked.symbols.iter().map(|(address, name)| format!("{} {}\n", address, name)).collect();
+        write_file(Path::new(&symbol_file), &symbols);
+    }
+    match output_file {
+        None => println!("{}", format_program(&linked.program)),
+        Some(output_file) => write_file(Path::new(&output_file), &format_program(&linked.program))
+    };
+}
 
-            let original_pc = memory.program_counter;
-            instruction.execute(&mut memory, &mut input_iterator, output);
-            
-            // Only incrememnt if the instruction didn't modify the pc
-            if original_pc == memory.program_counter {
-                memory.program_counter += (instruction.parameter_count + 1) as usize;
+// transpile <program file> [function name] [output file]
+fn run_transpile(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let function_name = args.get(1).map(String::as_str).unwrap_or("run_intcode");
+
+    let computer = IntcodeComputer::with_profile(Profile::Diagnostic);
+    let transpiled = transpile::transpile(&computer, &program, function_name);
+    if !transpiled.compiled {
+        eprintln!("The program modifies its own code, so it has been transpiled to an interpreter");
+    }
+
+    match args.get(2) {
+        None => print!("{}", transpiled.source),
+        Some(output_file) => write_file(Path::new(output_file), &transpiled.source)
+    };
+}
+
+// amplifiers <program file> <phase>,... [--feedback] [--quantum=<n>] [--threads] [--timeout=<milliseconds>]
+// Runs a copy of the program for each phase setting, each one's output going to the next, with the
+// first given 0 to start. With --feedback, the last one's output goes back to the first too. They
+// take turns on one thread, or with --threads each gets a thread of its own.
+fn run_amplifiers(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let phases = parse_program(&args[1]);
+    let (quantum, arguments) = option_argument(&args[2..], "--quantum=");
+    let (timeout, arguments) = option_argument(&arguments, "--timeout=");
+    let feedback = arguments.iter().any(|argument| argument == "--feedback");
+    let threads = arguments.iter().any(|argument| argument == "--threads");
+    let quantum = quantum.map(|quantum| parse_address(&quantum)).unwrap_or(100);
+    let timeout = Duration::from_millis(timeout.map(|timeout| parse_address(&timeout)).unwrap_or(1000) as u64);
+
+    // Each amplifier sends to the next
+    let last = phases.len() - 1;
+    let mut links: Vec<(usize, usize)> = (0..last).map(|index| (index, index + 1)).collect();
+    if feedback {
+        links.push((last, 0));
+    }
+
+    let computer = make_computer(Profile::Diagnostic);
+    let output = if threads {
+        let mut runtime = threaded::Runtime::new(&computer, timeout);
+        for &phase in &phases {
+            let amplifier = runtime.spawn(&program);
+            runtime.send(amplifier, phase);
+        }
+        for &(from, to) in &links {
+            runtime.connect(from, to);
+        }
+        runtime.send(0, 0);
+
+        let mut results = runtime.run();
+        for (index, result) in results.iter().enumerate() {
+            if let Err(why) = result {
+                panic!("Amplifier {} failed: {}", index, why);
             }
         }
+        results.pop().unwrap().unwrap()
+    } else {
+        let mut scheduler = scheduler::Scheduler::new(&computer, quantum);
+        for &phase in &phases {
+            let amplifier = scheduler.spawn(&program);
+            scheduler.send(amplifier, phase);
+        }
+        for &(from, to) in &links {
+            scheduler.connect(from, to);
+        }
+        scheduler.send(0, 0);
+
+        if let Err(why) = scheduler.run() {
+            panic!("{}", why);
+        }
+        scheduler.output(last).to_vec()
+    };
+
+    match output.last() {
+        None => panic!("The last amplifier didn't output anything!"),
+        Some(signal) => println!("Output:\n{}", signal)
+    };
+}
 
-        return memory;
+// ascii <program file> [--profile=<name>]
+// Runs an ASCII program in the terminal, giving it each line typed whenever it wants input
+fn run_ascii(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let (profile, _) = option_argument(&args[1..], "--profile=");
+    let computer = make_computer(profile.map(|name| parse_profile(&name)).unwrap_or(Profile::Extended));
+
+    let mut stream = ascii::AsciiStream::new(&computer, &program);
+    let mut buffer = [0; 1024];
+    loop {
+        let count = match stream.read(&mut buffer) {
+            Err(ref why) if why.kind() == io::ErrorKind::WouldBlock => {
+                let mut line = String::new();
+                match io::stdin().read_line(&mut line) {
+                    Err(why) => panic!("Couldn't read input: {}", why),
+                    Ok(0) => return,
+                    Ok(_) => ()
+                };
+                if let Err(why) = stream.write_all(line.as_bytes()) {
+                    panic!("Couldn't give the program input: {}", why);
+                }
+                continue;
+            },
+            Err(why) => panic!("{}", why),
+            Ok(0) => return,
+            Ok(count) => count
+        };
+
+        let mut stdout = io::stdout();
+        if let Err(why) = stdout.write_all(&buffer[..count]).and_then(|_| stdout.flush()) {
+            panic!("Couldn't write output: {}", why);
+        }
     }
 }
 
-fn make_instructions() -> Vec<Instruction> {
-    let mut instructions = Vec::new();
+// The cells from --from up to --to, or all of them
+fn address_range(args: &[String]) -> (Range<usize>, Vec<String>) {
+    let (from, arguments) = option_argument(args, "--from=");
+    let (to, arguments) = option_argument(&arguments, "--to=");
+    let from = from.map(|address| parse_address(&address)).unwrap_or(0);
+    let to = to.map(|address| parse_address(&address)).unwrap_or(usize::MAX);
+    return (from..to, arguments);
+}
+
+// Runs a program to the end, noting which instruction last wrote each cell and which ran
+fn run_with_history(computer: &mut IntcodeComputer, program: &Program, input: &[i32]) -> (Memory, hexdump::MemoryHistory) {
+    let history = Arc::new(Mutex::new(hexdump::MemoryHistory::new()));
+    computer.add_observer(history.clone());
 
-    // 1: p0 + p1 -> p2
-    instructions.push(Instruction::new_with_writes(1, 3, vec![2], |parameters, memory, _input, _output| {
-        memory.write(parameters[2], parameters[0] + parameters[1]);
-    }));
+    let mut output = Vec::new();
+    let memory = computer.execute(program, &input.to_vec(), &mut output);
 
-    // 2: p0 * p1 -> p2
-    instructions.push(Instruction::new_with_writes(2, 3, vec![2], |parameters, memory, _input, _output| {
-        memory.write(parameters[2], parameters[0] * parameters[1]);
-    }));
+    let history = match history.lock() {
+        Err(why) => panic!("History is unusable: {}", why),
+        Ok(mut history) => mem::replace(&mut *history, hexdump::MemoryHistory::new())
+    };
+    return (memory, history);
+}
 
-    // 3: input -> p0
-    instructions.push(Instruction::new_with_writes(3, 1, vec![0], |parameters, memory, input, _output| {
-        let value = input.next().unwrap();
-        memory.write(parameters[0], *value);
-    }));
+// dump <program file> [--profile=<name>] [--from=<address>] [--to=<address>] [inputs]...
+// Shows memory as a grid of cells, after running the program if any inputs are given
+fn run_dump(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let (profile, arguments) = option_argument(&args[1..], "--profile=");
+    let (range, input_arguments) = address_range(&arguments);
+    let mut computer = make_computer(profile.map(|name| parse_profile(&name)).unwrap_or(Profile::Diagnostic));
+
+    let (memory, history) = if input_arguments.is_empty() {
+        (Memory::initialize(&program), hexdump::MemoryHistory::new())
+    } else {
+        run_with_history(&mut computer, &program, &parse_inputs(&input_arguments))
+    };
+    print!("{}", hexdump::dump(&computer, &memory, &history, range));
+}
 
-    // 4: p0 -> output
-    instructions.push(Instruction::new(4, 1, |parameters, _memory, _input, output| {
-        output.push(parameters[0]);
-    }));
+// diff <program file> [--profile=<name>] [--from=<address>] [--to=<address>] [--against=<input>,...] [inputs]...
+// Shows the cells a run changed, and the instruction that last wrote each. With --against, compares
+// the memory after two runs with different input instead.
+fn run_diff(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let (profile, arguments) = option_argument(&args[1..], "--profile=");
+    let (against, arguments) = option_argument(&arguments, "--against=");
+    let (range, input_arguments) = address_range(&arguments);
+    let profile = profile.map(|name| parse_profile(&name)).unwrap_or(Profile::Diagnostic);
+
+    let after = run_with_history(&mut make_computer(profile), &program, &parse_inputs(&input_arguments));
+    let before = match against {
+        None => (Memory::initialize(&program), hexdump::MemoryHistory::new()),
+        Some(against) => run_with_history(&mut make_computer(profile), &program, &parse_program(&against))
+    };
+
+    let changes = hexdump::diff((&before.0, &before.1), (&after.0, &after.1), range);
+    for change in &changes {
+        println!("{}", change);
+    }
+    println!("{} cells changed", changes.len());
+}
 
-    // 5: if p0 != 0, p1 -> PC
-    instructions.push(Instruction::new(5, 2, |parameters, memory, _input, _output| {
-        if parameters[0] != 0 {
-            memory.program_counter = parameters[1] as usize;
-        }
-    }));
-    
-    // 6: if p0 == 0, p1 -> PC
-    instructions.push(Instruction::new(6, 2, |parameters, memory, _input, _output| {
-        if parameters[0] == 0 {
-            memory.program_counter = parameters[1] as usize;
-        }
-    }));
-    
-    // 7: if p0 < p1, 1 -> p3 else 0 -> p3
-    instructions.push(Instruction::new_with_writes(7, 3, vec![2], |parameters, memory, _input, _output| {
-        if parameters[0] < parameters[1] {
-            memory.write(parameters[2], 1);
-        } else {
-            memory.write(parameters[2], 0);
-        }
-    }));
-    
-    // 8: if p0 == p1, 1 -> p3 else 0 -> p3
-    instructions.push(Instruction::new_with_writes(8, 3, vec![2], |parameters, memory, _input, _output| {
-        if parameters[0] == parameters[1] {
-            memory.write(parameters[2], 1);
-        } else {
-            memory.write(parameters[2], 0);
+// diagnose <program file> [system ids]...
+// Runs a Day 5 diagnostic program once for each system id (1 and 5 if none are given), checking every
+// test passes, so diagnostic programs can be used to check changes to the computer
+fn run_diagnose(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let mut system_ids = parse_inputs(&args[1..]);
+    if system_ids.is_empty() {
+        system_ids = vec![1, 5];
+    }
+
+    let mut failures = 0;
+    for system_id in system_ids {
+        let mut computer = IntcodeComputer::with_profile(Profile::Diagnostic);
+        computer.set_trace(false);
+        let sites = Arc::new(Mutex::new(diagnostic::OutputSites::new()));
+        computer.add_observer(sites.clone());
+
+        // A non-zero output followed by another is a failed test, and nothing after that matters
+        let input = vec![system_id];
+        let mut output = Vec::new();
+        for value in computer.run_iter(&program, &input) {
+            match value {
+                Err(why) => panic!("{}", why),
+                Ok(value) => output.push(value)
+            };
+            if output.len() > 1 && output[output.len() - 2] != 0 {
+                break;
+            }
         }
-    }));
 
-    return instructions;
+        let result = match sites.lock() {
+            Err(why) => panic!("Output sites are unusable: {}", why),
+            Ok(sites) => diagnostic::check(&output, &sites)
+        };
+        match result {
+            Err(why) => {
+                failures += 1;
+                println!("System {}: {}", system_id, why);
+            },
+            Ok(code) => println!("System {}: passed {} tests, diagnostic code {}", system_id, output.len() - 1, code)
+        };
+    }
+
+    if failures > 0 {
+        panic!("{} diagnostic runs failed", failures);
+    }
 }
 
-fn read_program() -> Vec<i32> {
-    return vec![3,225,1,225,6,6,1100,1,238,225,104,0,1002,43,69,224,101,-483,224,224,4,224,1002,223,8,223,1001,224,5,224,1,224,223,223,1101,67,60,225,1102,5,59,225,1101,7,16,225,1102,49,72,225,101,93,39,224,101,-98,224,224,4,224,102,8,223,223,1001,224,6,224,1,224,223,223,1102,35,82,225,2,166,36,224,101,-4260,224,224,4,224,102,8,223,223,101,5,224,224,1,223,224,223,102,66,48,224,1001,224,-4752,224,4,224,102,8,223,223,1001,224,2,224,1,223,224,223,1001,73,20,224,1001,224,-55,224,4,224,102,8,223,223,101,7,224,224,1,223,224,223,1102,18,41,224,1001,224,-738,224,4,224,102,8,223,223,101,6,224,224,1,224,223,223,1101,68,71,225,1102,5,66,225,1101,27,5,225,1101,54,63,224,1001,224,-117,224,4,224,102,8,223,223,1001,224,2,224,1,223,224,223,1,170,174,224,101,-71,224,224,4,224,1002,223,8,223,1001,224,4,224,1,223,224,223,4,223,99,0,0,0,677,0,0,0,0,0,0,0,0,0,0,0,1105,0,99999,1105,227,247,1105,1,99999,1005,227,99999,1005,0,256,1105,1,99999,1106,227,99999,1106,0,265,1105,1,99999,1006,0,99999,1006,227,274,1105,1,99999,1105,1,280,1105,1,99999,1,225,225,225,1101,294,0,0,105,1,0,1105,1,99999,1106,0,300,1105,1,99999,1,225,225,225,1101,314,0,0,106,0,0,1105,1,99999,1007,226,226,224,1002,223,2,223,1006,224,329,1001,223,1,223,1007,226,677,224,102,2,223,223,1006,224,344,1001,223,1,223,108,677,677,224,102,2,223,223,1005,224,359,1001,223,1,223,1007,677,677,224,1002,223,2,223,1006,224,374,101,1,223,223,8,677,226,224,1002,223,2,223,1006,224,389,101,1,223,223,7,226,226,224,1002,223,2,223,1005,224,404,101,1,223,223,7,677,226,224,102,2,223,223,1005,224,419,1001,223,1,223,8,226,677,224,1002,223,2,223,1005,224,434,101,1,223,223,1008,226,677,224,102,2,223,223,1006,224,449,1001,223,1,223,7,226,677,224,1002,223,2,223,1006,224,464,1001,223,1,223,108,677,226,224,102,2,223,223,1005,224,479,101,1,223,223,108,226,226,224,1002,223,2,223,1006,224,494,101,1,223,223,8,226,226,224,1002,223,2,223,1005,224,509,1001,223,1,223,1107,677,226,224,102,2,223,223,1005,224,524,1001,223,1,223,1107,226,226,224,102,2,223,223,1005,224,539,1001,223,1,223,1108,677,677,224,1002,223,2,223,1006,224,554,101,1,223,223,107,226,677,224,102,2,223,223,1005,224,569,1001,223,1,223,1108,226,677,224,1002,223,2,223,1005,224,584,1001,223,1,223,1107,226,677,224,1002,223,2,223,1005,224,599,1001,223,1,223,1008,226,226,224,1002,223,2,223,1005,224,614,101,1,223,223,107,226,226,224,102,2,223,223,1006,224,629,1001,223,1,223,1008,677,677,224,1002,223,2,223,1006,224,644,101,1,223,223,107,677,677,224,1002,223,2,223,1005,224,659,101,1,223,223,1108,677,226,224,1002,223,2,223,1006,224,674,1001,223,1,223,4,223,99,226];
+// minimise <program file> [--profile=<name>] [--max-steps=<n>] [--output=<file>] [inputs]...
+// Shrinks a program that fails, or gives the wrong output, to something small enough to read,
+// keeping whatever it did the same
+fn run_minimise(args: &[String]) {
+    let program = read_program_file(Path::new(&args[0]));
+    let (profile, arguments) = option_argument(&args[1..], "--profile=");
+    let (step_limit, arguments) = option_argument(&arguments, "--max-steps=");
+    let (output_file, input_arguments) = option_argument(&arguments, "--output=");
+    let profile = profile.map(|name| parse_profile(&name)).unwrap_or(Profile::Diagnostic);
+    let step_limit = step_limit.map(|limit| parse_address(&limit)).unwrap_or(1_000_000);
+    let input = parse_inputs(&input_arguments);
+
+    let minimised = minimise::minimise(make_computer(profile), &program, &input, step_limit);
+    println!("The program {}", minimised.outcome);
+    println!("Shrank {} cells and {} inputs to {} cells and {} inputs in {} runs",
+             program.len(), input.len(), minimised.program.len(), minimised.input.len(), minimised.runs);
+    println!("Input:\n{}", format_program(&minimised.input));
+    match output_file {
+        None => println!("Program:\n{}", format_program(&minimised.program)),
+        Some(output_file) => write_file(Path::new(&output_file), &format_program(&minimised.program))
+    };
+}
+
+// fuzz <basic|diagnostic|extended|hosted|all> [--programs=<n>] [--seed=<n>]
+// Checks the computer against random programs. A failing program can be checked again on its own
+// with the seed it is reported with and --programs=1.
+fn run_fuzz(args: &[String]) {
+    let (programs, arguments) = option_argument(args, "--programs=");
+    let (seed, arguments) = option_argument(&arguments, "--seed=");
+    let programs = programs.map(|count| parse_address(&count)).unwrap_or(1000);
+    let seed: i32 = match seed.unwrap_or(String::from("1")).parse() {
+        Err(why) => panic!("Couldn't parse seed: {}", why),
+        Ok(seed) => seed
+    };
+
+    let profiles = match arguments[0].as_str() {
+        "all" => vec![Profile::Basic, Profile::Diagnostic, Profile::Extended, Profile::Hosted],
+        name => vec![parse_profile(name)]
+    };
+
+    let mut failures = 0;
+    for profile in profiles {
+        for index in 0..programs {
+            let program_seed = seed.wrapping_add(index as i32);
+            // Only sum and max from host_functions, since print would fill the screen
+            let generated = generator::Generator::new(profile, program_seed, vec![1, 2]).generate();
+            if let Err(failure) = fuzz::check(&generated, profile, &make_computer) {
+                failures += 1;
+                println!("{} seed {}: {} failed: {}", profile.name(), program_seed, failure.property, failure.detail);
+                println!("    program {}", format_program(&generated.program));
+                println!("    input {}", format_program(&generated.input));
+            }
+        }
+        println!("Checked {} {} programs", programs, profile.name());
+    }
+
+    if failures > 0 {
+        panic!("{} programs failed", failures);
+    }
 }
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 2 {
+        match args[1].as_str() {
+            "run" => run_program(&args[2..]),
+            "screen" => run_screen(&args[2..]),
+            "replay" => run_replay(&args[2..]),
+            "robot" => run_robot(&args[2..]),
+            "maze" => run_maze(&args[2..]),
+            "minimise" => run_minimise(&args[2..]),
+            "amplifiers" => run_amplifiers(&args[2..]),
+            "analyse" => run_analyse(&args[2..]),
+            "ascii" => run_ascii(&args[2..]),
+            "compile" => run_compile(&args[2..]),
+            "coverage" => run_coverage(&args[2..]),
+            "fuzz" => run_fuzz(&args[2..]),
+            "decompile" => run_decompile(&args[2..]),
+            "diagnose" => run_diagnose(&args[2..]),
+            "diff" => run_diff(&args[2..]),
+            "dump" => run_dump(&args[2..]),
+            "link" => run_link(&args[2..]),
+            "optimise" => run_optimise(&args[2..]),
+            "pack" => run_pack(&args[2..]),
+            "transpile" => run_transpile(&args[2..]),
+            "unpack" => run_unpack(&args[2..]),
+            command => panic!("Unknown command: {}", command)
+        }
+        return;
+    }
 
-    let computer = IntcodeComputer::new(make_instructions());
+    let computer = IntcodeComputer::with_profile(Profile::Diagnostic);
 
     let input = vec![5];
     let mut output = Vec::new();
@@ -0,0 +1,175 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use super::grid::{self, Direction, Point};
+use super::{ExecutionState, IntcodeComputer, IntcodeError, Memory, Program};
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Wall,
+    Open,
+    Target
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MazeError {
+    Failed(IntcodeError),
+    // The droid answered a move with something other than 0, 1 or 2, or with nothing at all
+    UnknownStatus { position: Point, status: Option<i32> }
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MazeError::Failed(error) => write!(f, "{}", error),
+            MazeError::UnknownStatus { position, status: Some(status) } => write!(f, "Unknown droid status after moving to {}: {}!", position, status),
+            MazeError::UnknownStatus { position, status: None } => write!(f, "No droid status after moving to {}!", position)
+        }
+    }
+}
+
+// The program takes a movement command (1 north, 2 south, 3 west, 4 east) and replies with
+// 0 (hit a wall), 1 (moved) or 2 (moved and found the target)
+fn movement_command(direction: Direction) -> i32 {
+    return match direction {
+        Direction::Up => 1,
+        Direction::Down => 2,
+        Direction::Left => 3,
+        Direction::Right => 4
+    };
+}
+
+pub struct Maze {
+    cells: HashMap<Point, Cell>,
+    target: Option<Point>
+}
+
+impl Maze {
+    // Explores breadth first from (0, 0). Every open cell keeps a copy of the droid that is
+    // standing on it, so stepping into a neighbour is one command on a clone of that droid
+    // rather than walking back there from the start.
+    pub fn explore(computer: &IntcodeComputer, program: &Program) -> Result<Maze, MazeError> {
+        let origin = Point::new(0, 0);
+        let mut cells = HashMap::new();
+        let mut target = None;
+        cells.insert(origin, Cell::Open);
+
+        let mut frontier = VecDeque::new();
+        frontier.push_back((origin, Memory::initialize(program)));
+
+        while let Some((position, droid)) = frontier.pop_front() {
+            for &direction in DIRECTIONS.iter() {
+                let next = position + direction.delta();
+                if cells.contains_key(&next) {
+                    continue;
+                }
+
+                let mut next_droid = droid.clone();
+                let command = [movement_command(direction)];
+                let mut output = Vec::new();
+                let state = match computer.resume(&mut next_droid, &mut command.iter(), &mut output) {
+                    Err(why) => return Err(MazeError::Failed(why)),
+                    Ok(state) => state
+                };
+
+                let cell = match output.last() {
+                    Some(0) => Cell::Wall,
+                    Some(1) => Cell::Open,
+                    Some(2) => Cell::Target,
+                    status => return Err(MazeError::UnknownStatus {position: next, status: status.cloned()})
+                };
+
+                cells.insert(next, cell);
+                if cell == Cell::Target {
+                    target = Some(next);
+                }
+                if cell != Cell::Wall && state == ExecutionState::AwaitingInput {
+                    frontier.push_back((next, next_droid));
+                }
+            }
+        }
+
+        return Ok(Maze {cells, target});
+    }
+
+    pub fn cell(&self, point: Point) -> Option<Cell> {
+        return self.cells.get(&point).cloned();
+    }
+
+    pub fn target(&self) -> Option<Point> {
+        return self.target;
+    }
+
+    // Breadth first search over the discovered map, returning the moves to get from one cell to another
+    pub fn shortest_path(&self, from: Point, to: Point) -> Option<Vec<Direction>> {
+        let mut came_from: HashMap<Point, (Point, Direction)> = HashMap::new();
+        let mut frontier = VecDeque::new();
+        frontier.push_back(from);
+
+        while let Some(position) = frontier.pop_front() {
+            if position == to {
+                let mut path = Vec::new();
+                let mut current = to;
+                while current != from {
+                    let (previous, direction) = came_from[&current];
+                    path.push(direction);
+                    current = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for &direction in DIRECTIONS.iter() {
+                let next = position + direction.delta();
+                if next != from && !came_from.contains_key(&next) && self.is_open(next) {
+                    came_from.insert(next, (position, direction));
+                    frontier.push_back(next);
+                }
+            }
+        }
+
+        return None;
+    }
+
+    pub fn path_to_target(&self) -> Option<Vec<Direction>> {
+        return self.target.and_then(|target| self.shortest_path(Point::new(0, 0), target));
+    }
+
+    // How many steps it takes to flood every reachable open cell starting from the given one
+    pub fn flood_fill_distance(&self, from: Point) -> usize {
+        let mut distances = HashMap::new();
+        let mut frontier = VecDeque::new();
+        distances.insert(from, 0);
+        frontier.push_back(from);
+
+        while let Some(position) = frontier.pop_front() {
+            let distance = distances[&position];
+            for &direction in DIRECTIONS.iter() {
+                let next = position + direction.delta();
+                if !distances.contains_key(&next) && self.is_open(next) {
+                    distances.insert(next, distance + 1);
+                    frontier.push_back(next);
+                }
+            }
+        }
+
+        return *distances.values().max().unwrap();
+    }
+
+    fn is_open(&self, point: Point) -> bool {
+        return matches!(self.cell(point), Some(Cell::Open) | Some(Cell::Target));
+    }
+
+    pub fn render(&self) -> String {
+        let points: Vec<Point> = self.cells.keys().cloned().collect();
+        return grid::render(&points, |point| match self.cell(point) {
+            _ if point == Point::new(0, 0) => 'D',
+            Some(Cell::Wall) => '#',
+            Some(Cell::Open) => '.',
+            Some(Cell::Target) => 'O',
+            None => ' '
+        });
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use super::intcode::{Instruction, Observer, Verdict};
+use super::watch::StepLimit;
+use super::{ExecutionState, IntcodeComputer, IntcodeError, Memory, Profile, Program};
+
+// Writes further than this past the end of the program are stopped, since a program with cells
+// zeroed or jumps removed can easily try to grow memory to billions of cells
+const MEMORY_SLACK: usize = 1 << 20;
+
+// What a run did, which the minimised program has to keep doing
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Halted(Vec<i32>),
+    AwaitingInput(Vec<i32>),
+    Failed(IntcodeError),
+    // Still running at the step limit
+    TooLong,
+    // Stopped before writing somewhere absurd
+    Escaped
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Halted(output) => write!(f, "halted with output {:?}", output),
+            Outcome::AwaitingInput(output) => write!(f, "ran out of input with output {:?}", output),
+            Outcome::Failed(why) => write!(f, "failed: {}", why),
+            Outcome::TooLong => write!(f, "was still running at the step limit"),
+            Outcome::Escaped => write!(f, "tried to write far past the end of its memory")
+        }
+    }
+}
+
+// Aborts instructions that would write past limit
+struct Confinement {
+    profile: Profile,
+    limit: usize
+}
+
+impl Observer for Confinement {
+    fn before_instruction(&mut self, memory: &Memory, instruction: &Instruction) -> Verdict {
+        for &parameter in &instruction.write_parameters {
+            if let Ok(address) = memory.parameter_address(parameter, self.profile) {
+                if address as i64 > self.limit as i64 {
+                    return Verdict::Abort;
+                }
+            }
+        }
+        return Verdict::Continue;
+    }
+}
+
+pub struct Minimised {
+    pub program: Program,
+    pub input: Vec<i32>,
+    pub outcome: Outcome,
+    pub runs: usize
+}
+
+struct Minimiser {
+    computer: IntcodeComputer,
+    step_limit: usize,
+    target: Outcome,
+    runs: usize
+}
+
+impl Minimiser {
+    fn run(&mut self, program: &Program, input: &[i32]) -> Outcome {
+        self.runs += 1;
+        let mut memory = Memory::initialize(program);
+        let mut output = Vec::new();
+        return match self.computer.resume(&mut memory, &mut input.iter(), &mut output) {
+            Err(IntcodeError::Aborted(_)) if memory.steps >= self.step_limit => Outcome::TooLong,
+            Err(IntcodeError::Aborted(_)) => Outcome::Escaped,
+            Err(why) => Outcome::Failed(why),
+            Ok(ExecutionState::AwaitingInput) => Outcome::AwaitingInput(output),
+            Ok(_) => Outcome::Halted(output)
+        };
+    }
+
+    fn still_fails(&mut self, program: &Program, input: &[i32]) -> bool {
+        return self.run(program, input) == self.target;
+    }
+
+    // Removes ever smaller chunks of the input, keeping each removal that still fails the same way
+    fn shorten_input(&mut self, program: &Program, mut input: Vec<i32>) -> Vec<i32> {
+        let mut chunk = input.len().next_power_of_two();
+        while chunk >= 1 && !input.is_empty() {
+            let mut start = 0;
+            while start < input.len() {
+                let end = (start + chunk).min(input.len());
+                let mut candidate = input.clone();
+                candidate.drain(start..end);
+                if self.still_fails(program, &candidate) {
+                    input = candidate;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+        return input;
+    }
+
+    // Zeroes ever smaller chunks of the program, keeping each change that still fails the same way
+    fn zero_cells(&mut self, mut program: Program, input: &[i32]) -> Program {
+        let mut chunk = program.len().next_power_of_two();
+        while chunk >= 1 {
+            for start in (0..program.len()).step_by(chunk) {
+                let end = (start + chunk).min(program.len());
+                if program[start..end].iter().all(|&value| value == 0) {
+                    continue;
+                }
+
+                let mut candidate = program.clone();
+                candidate[start..end].iter_mut().for_each(|value| *value = 0);
+                if self.still_fails(&candidate, input) {
+                    program = candidate;
+                }
+            }
+            chunk /= 2;
+        }
+        return program;
+    }
+
+    // Anything that looks like a jump might be one, so try making each of them halt instead
+    fn halt_jumps(&mut self, mut program: Program, input: &[i32]) -> Program {
+        for address in 0..program.len() {
+            if program[address] > 0 && [5, 6].contains(&Instruction::decode_opcode(program[address])) {
+                let mut candidate = program.clone();
+                candidate[address] = 99;
+                if self.still_fails(&candidate, input) {
+                    program = candidate;
+                }
+            }
+        }
+        return program;
+    }
+}
+
+// Shrinks a program and its input as far as possible while it still does exactly what it did: the
+// same error or the same output. Runs stop after step_limit instructions, so
+// programs that never halt can be minimised too.
+pub fn minimise(mut computer: IntcodeComputer, program: &Program, input: &[i32], step_limit: usize) -> Minimised {
+    let profile = computer.profile;
+    computer.add_observer(Arc::new(Mutex::new(StepLimit::new(step_limit))));
+    computer.add_observer(Arc::new(Mutex::new(Confinement {profile, limit: program.len() + MEMORY_SLACK})));
+
+    let mut minimiser = Minimiser {computer, step_limit, target: Outcome::Escaped, runs: 0};
+    minimiser.target = minimiser.run(program, input);
+
+    let mut program = program.clone();
+    let mut input = input.to_vec();
+    loop {
+        let before = (program.clone(), input.clone());
+        input = minimiser.shorten_input(&program, input);
+        program = minimiser.halt_jumps(program, &input);
+        program = minimiser.zero_cells(program, &input);
+
+        // Memory past the end reads as zero anyway
+        while program.last() == Some(&0) {
+            program.pop();
+        }
+
+        if (program.clone(), input.clone()) == before {
+            break;
+        }
+    }
+
+    return Minimised {program, input, outcome: minimiser.target, runs: minimiser.runs};
+}
@@ -0,0 +1,374 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::analysis::{self, Analysis, Firing};
+use super::disassembly::{self, ControlFlow, DecodedInstruction, Operand};
+use super::{IntcodeComputer, Program};
+
+// Things the optimiser changed in the program
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rewrite {
+    // A position mode read of a cell that is never written became an immediate read of its value
+    PropagatedConstant { address: usize, cell: usize, value: i32 },
+    // An arithmetic or comparison instruction with only immediate inputs became `1101,value,0,destination`
+    FoldedConstant { address: usize, value: i32, destination: usize },
+    // A jump that landed on an unconditional jump now goes straight to where that one goes
+    ThreadedJump { address: usize, from: usize, to: usize },
+    // A run of additions into the same cell became one addition followed by a jump past the rest
+    FoldedAdditions { address: usize, cell: usize, count: usize, total: i32 },
+    // A jump whose condition always comes out the same way got an immediate condition instead
+    ResolvedJump { address: usize, fires: bool }
+}
+
+impl fmt::Display for Rewrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rewrite::PropagatedConstant { address, cell, value } =>
+                write!(f, "{:>5}: [{}] is never written, read it as #{}", address, cell, value),
+            Rewrite::FoldedConstant { address, value, destination } =>
+                write!(f, "{:>5}: folded constant store of {} into [{}]", address, value, destination),
+            Rewrite::ThreadedJump { address, from, to } =>
+                write!(f, "{:>5}: jump to {} only jumps on to {}, going there directly", address, from, to),
+            Rewrite::FoldedAdditions { address, cell, count, total } =>
+                write!(f, "{:>5}: folded {} additions into [{}] into one adding {}", address, count, cell, total),
+            Rewrite::ResolvedJump { address, fires } =>
+                write!(f, "{:>5}: jump {} fires, so its condition is now a constant", address, if *fires { "always" } else { "never" })
+        }
+    }
+}
+
+// Idioms that were recognised but didn't need changing
+#[derive(Clone, Debug, PartialEq)]
+pub enum Observation {
+    UnconditionalJump { address: usize, target: Operand },
+    NeverTakenJump { address: usize },
+    // Nothing is safe to change if we can't tell what might run
+    SelfModifyingCode { address: usize },
+    UnresolvedJump { address: usize },
+    // An instruction that following the jumps finds, but that no run of the program can reach
+    UnreachableCode { address: usize }
+}
+
+impl fmt::Display for Observation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Observation::UnconditionalJump { address, target } => write!(f, "{:>5}: unconditional jump to {}", address, target),
+            Observation::NeverTakenJump { address } => write!(f, "{:>5}: jump is never taken", address),
+            Observation::SelfModifyingCode { address } =>
+                write!(f, "{:>5}: instruction is overwritten at runtime, so the program can't be optimised", address),
+            Observation::UnresolvedJump { address } =>
+                write!(f, "{:>5}: jump target can't be worked out, so the program can't be optimised", address),
+            Observation::UnreachableCode { address } => write!(f, "{:>5}: instruction can never run", address)
+        }
+    }
+}
+
+pub struct OptimisationReport {
+    pub rewrites: Vec<Rewrite>,
+    pub observations: Vec<Observation>
+}
+
+impl fmt::Display for OptimisationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} rewrites:", self.rewrites.len())?;
+        for rewrite in &self.rewrites {
+            writeln!(f, "{}", rewrite)?;
+        }
+
+        writeln!(f, "{} observations:", self.observations.len())?;
+        for observation in &self.observations {
+            writeln!(f, "{}", observation)?;
+        }
+
+        return Ok(());
+    }
+}
+
+// What we need to know about the original program to decide whether touching a cell is safe
+struct Safety {
+    control_flow: ControlFlow,
+    written: BTreeSet<usize>,
+    data_reads: BTreeSet<usize>
+}
+
+impl Safety {
+    fn new(computer: &IntcodeComputer, program: &Program) -> Safety {
+        let control_flow = disassembly::trace_control_flow(computer, program);
+        let written = control_flow.write_targets();
+        let data_reads = control_flow.data_reads();
+        return Safety {control_flow, written, data_reads};
+    }
+
+    // An instruction can be rewritten if nothing modifies it or reads it as data
+    fn can_rewrite(&self, instruction: &DecodedInstruction) -> bool {
+        return instruction.cells().all(|cell| !self.written.contains(&cell) && !self.data_reads.contains(&cell));
+    }
+
+    // Whether execution can only enter these cells through the first one
+    fn single_entry(&self, start: usize, end: usize) -> bool {
+        return self.control_flow.jump_targets.range(start + 1..end).next().is_none();
+    }
+}
+
+pub fn encode(opcode: u8, operands: &[Operand]) -> Vec<i32> {
+    let mut value = opcode as i32;
+    let mut cells = Vec::new();
+    for (index, operand) in operands.iter().enumerate() {
+        match operand {
+            Operand::Immediate(parameter) => {
+                value += 10i32.pow(index as u32 + 2);
+                cells.push(*parameter);
+            },
+            Operand::Position(address) => cells.push(*address as i32)
+        };
+    }
+
+    cells.insert(0, value);
+    return cells;
+}
+
+fn replace(program: &mut Program, address: usize, cells: &[i32]) {
+    program[address..address + cells.len()].copy_from_slice(cells);
+}
+
+// If this instruction adds an immediate value to a cell in place, returns the cell and the value
+fn in_place_addition(instruction: &DecodedInstruction) -> Option<(usize, i32)> {
+    if instruction.opcode != 1 {
+        return None;
+    }
+
+    return match (instruction.operands[0], instruction.operands[1], instruction.operands[2]) {
+        (Operand::Position(a), Operand::Immediate(value), Operand::Position(destination)) if a == destination => Some((destination, value)),
+        (Operand::Immediate(value), Operand::Position(b), Operand::Position(destination)) if b == destination => Some((destination, value)),
+        _ => None
+    };
+}
+
+// If this instruction stores a constant, returns where and what
+fn constant_store(instruction: &DecodedInstruction) -> Option<(usize, i32)> {
+    let value = instruction.constant_result()?;
+    return Some((instruction.write_targets()[0], value));
+}
+
+struct Optimiser<'a> {
+    computer: &'a IntcodeComputer,
+    original: &'a Program,
+    safety: Safety,
+    analysis: Analysis,
+    program: Program,
+    report: OptimisationReport
+}
+
+impl<'a> Optimiser<'a> {
+    // Re-decodes an instruction from the program as it is now, since earlier passes may have changed it
+    fn current(&self, address: usize) -> Option<DecodedInstruction> {
+        return disassembly::decode(self.computer, &self.program, address);
+    }
+
+    fn rewritable_addresses(&self) -> Vec<usize> {
+        return self.safety.control_flow.instructions.values()
+                   .filter(|instruction| self.safety.can_rewrite(instruction))
+                   .map(|instruction| instruction.address)
+                   .collect();
+    }
+
+    fn propagate_constants(&mut self) {
+        for address in self.rewritable_addresses() {
+            let instruction = self.current(address).unwrap();
+
+            let mut operands = instruction.operands.clone();
+            for (index, operand) in operands.iter_mut().enumerate() {
+                if instruction.write_operands.contains(&(index as u8)) {
+                    continue;
+                }
+
+                if let Operand::Position(cell) = *operand {
+                    if cell < self.original.len() && !self.safety.written.contains(&cell) {
+                        let value = self.original[cell];
+                        *operand = Operand::Immediate(value);
+                        self.report.rewrites.push(Rewrite::PropagatedConstant {address, cell, value});
+                    }
+                }
+            }
+
+            if operands != instruction.operands {
+                replace(&mut self.program, address, &encode(instruction.opcode, &operands));
+            }
+        }
+    }
+
+    fn fold_constants(&mut self) {
+        for address in self.rewritable_addresses() {
+            let instruction = self.current(address).unwrap();
+            let (destination, value) = match constant_store(&instruction) {
+                None => continue,
+                Some(store) => store
+            };
+
+            let folded = encode(1, &[Operand::Immediate(value), Operand::Immediate(0), Operand::Position(destination)]);
+            if self.program[instruction.cells()] != folded[..] {
+                replace(&mut self.program, address, &folded);
+                self.report.rewrites.push(Rewrite::FoldedConstant {address, value, destination});
+            }
+        }
+    }
+
+    // Uses what the analysis found to give jumps that always or never fire a constant condition, so
+    // the other passes treat them like any other unconditional or never taken jump
+    fn resolve_jumps(&mut self) {
+        if !self.analysis.complete {
+            return;
+        }
+
+        for address in self.analysis.unreachable(&self.safety.control_flow) {
+            self.report.observations.push(Observation::UnreachableCode {address});
+        }
+
+        for (&address, &firing) in &self.analysis.jumps {
+            let fires = match firing {
+                Firing::Always => true,
+                Firing::Never => false,
+                Firing::Sometimes => continue
+            };
+
+            let jump = match disassembly::decode(self.computer, &self.program, address) {
+                Some(jump) if jump.is_jump() && jump.constant_condition().is_none() && self.safety.can_rewrite(&jump) => jump,
+                _ => continue
+            };
+
+            let condition = if (jump.opcode == 5) == fires { 1 } else { 0 };
+            replace(&mut self.program, address, &encode(jump.opcode, &[Operand::Immediate(condition), jump.operands[1]]));
+            self.report.rewrites.push(Rewrite::ResolvedJump {address, fires});
+        }
+    }
+
+    // Follows a chain of unconditional jumps (that nothing modifies) to where it finally ends up
+    fn final_destination(&self, target: usize) -> usize {
+        let mut visited = BTreeSet::new();
+        let mut destination = target;
+        while visited.insert(destination) {
+            let next = match self.current(destination) {
+                Some(ref jump) if jump.constant_condition() == Some(true) && self.safety.can_rewrite(jump) => jump.jump_target(),
+                _ => None
+            };
+
+            match next {
+                Some(Operand::Immediate(next)) if next >= 0 => destination = next as usize,
+                _ => break
+            };
+        }
+
+        return destination;
+    }
+
+    fn thread_jumps(&mut self) {
+        let jumps: Vec<usize> = self.safety.control_flow.instructions.values()
+                                    .filter(|instruction| instruction.is_jump())
+                                    .map(|instruction| instruction.address)
+                                    .collect();
+
+        for address in jumps {
+            let jump = self.current(address).unwrap();
+            match jump.constant_condition() {
+                Some(true) => self.report.observations.push(Observation::UnconditionalJump {address, target: jump.operands[1]}),
+                Some(false) => {
+                    self.report.observations.push(Observation::NeverTakenJump {address});
+                    continue;
+                },
+                None => ()
+            };
+
+            let target = match jump.operands[1] {
+                Operand::Immediate(target) if target >= 0 && self.safety.can_rewrite(&jump) => target as usize,
+                _ => continue
+            };
+
+            let destination = self.final_destination(target);
+            if destination != target {
+                self.program[address + 2] = destination as i32;
+                self.report.rewrites.push(Rewrite::ThreadedJump {address, from: target, to: destination});
+            }
+        }
+    }
+
+    fn fold_additions(&mut self) {
+        let candidates = self.rewritable_addresses();
+        let mut index = 0;
+        while index < candidates.len() {
+            let first = self.current(candidates[index]).unwrap();
+            index += 1;
+
+            let (cell, store) = match (constant_store(&first), in_place_addition(&first)) {
+                (Some((cell, value)), _) => (cell, Some(value)),
+                (None, Some((cell, _))) => (cell, None),
+                _ => continue
+            };
+
+            let mut chain = vec![first];
+            while index < candidates.len() && candidates[index] == chain.last().unwrap().next_address() {
+                let next = self.current(candidates[index]).unwrap();
+                match in_place_addition(&next) {
+                    Some((next_cell, _)) if next_cell == cell => chain.push(next),
+                    _ => break
+                };
+                index += 1;
+            }
+
+            let start = chain[0].address;
+            let end = chain.last().unwrap().next_address();
+            let additions: Vec<i32> = chain.iter().filter_map(|instruction| in_place_addition(instruction).map(|(_, value)| value)).collect();
+            let total = additions.iter().try_fold(store.unwrap_or(0), |total, &value| total.checked_add(value));
+
+            // Folding two instructions would still leave two to execute (the addition and a jump)
+            if chain.len() < 3 || (start..end).contains(&cell) || !self.safety.single_entry(start, end) {
+                continue;
+            }
+
+            let total = match total {
+                None => continue,
+                Some(total) => total
+            };
+
+            let folded = match store {
+                Some(_) => encode(1, &[Operand::Immediate(total), Operand::Immediate(0), Operand::Position(cell)]),
+                None => encode(1, &[Operand::Position(cell), Operand::Immediate(total), Operand::Position(cell)])
+            };
+            replace(&mut self.program, start, &folded);
+            replace(&mut self.program, chain[1].address, &encode(5, &[Operand::Immediate(1), Operand::Immediate(end as i32)]));
+
+            self.report.rewrites.push(Rewrite::FoldedAdditions {address: start, cell, count: chain.len(), total});
+        }
+    }
+}
+
+// Returns an equivalent program that executes fewer instructions, or reads memory less, and what changed.
+// Only cells that are never written and never read as data are touched, and a run of instructions is only
+// collapsed if nothing can jump into the middle of it. If the program rewrites its own instructions, or
+// jumps somewhere we can't work out, we can't know which cells are safe, so it is returned unchanged.
+// Jumps are also resolved using the value sets from the analysis, which holds whatever the input is.
+pub fn optimise(computer: &IntcodeComputer, program: &Program) -> (Program, OptimisationReport) {
+    let mut optimiser = Optimiser {
+        computer,
+        original: program,
+        safety: Safety::new(computer, program),
+        analysis: analysis::analyse(computer, program, None),
+        program: program.clone(),
+        report: OptimisationReport {rewrites: Vec::new(), observations: Vec::new()}
+    };
+
+    if !optimiser.safety.control_flow.is_complete() {
+        let control_flow = &optimiser.safety.control_flow;
+        let self_modifying = control_flow.modified_instructions.iter().map(|&address| Observation::SelfModifyingCode {address});
+        let unresolved = control_flow.unresolved_jumps.iter().map(|&address| Observation::UnresolvedJump {address});
+        optimiser.report.observations = self_modifying.chain(unresolved).collect();
+        return (optimiser.program, optimiser.report);
+    }
+
+    optimiser.propagate_constants();
+    optimiser.fold_constants();
+    optimiser.resolve_jumps();
+    optimiser.thread_jumps();
+    optimiser.fold_additions();
+
+    return (optimiser.program, optimiser.report);
+}
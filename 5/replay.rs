@@ -0,0 +1,126 @@
+use std::fmt;
+
+use super::intcode::{Observer, Profile, Verdict};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    // Something opcode 3 consumed, and how many instructions had run before it
+    Input { step: usize, value: i32 },
+    Output { step: usize, value: i32 }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Input { step, value } => write!(f, "in {} {}", step, value),
+            Event::Output { step, value } => write!(f, "out {} {}", step, value)
+        }
+    }
+}
+
+// Every value a run read and wrote, in order, so the run can be repeated exactly
+#[derive(Clone, Debug)]
+pub struct Recording {
+    // The instruction set the run used, so a replay runs the same one
+    pub profile: Profile,
+    pub events: Vec<Event>
+}
+
+impl Observer for Recording {
+    fn input(&mut self, step: usize, value: i32) -> Verdict {
+        self.events.push(Event::Input {step, value});
+        return Verdict::Continue;
+    }
+
+    fn output(&mut self, step: usize, value: i32) -> Verdict {
+        self.events.push(Event::Output {step, value});
+        return Verdict::Continue;
+    }
+}
+
+impl Recording {
+    pub fn new(profile: Profile) -> Recording {
+        return Recording {profile, events: Vec::new()};
+    }
+
+    // One event per line, for example "in 12 5" or "out 40 3508186", after a "profile <name>" line.
+    // Recordings without a profile line are from the diagnostic profile. Lines starting with # are ignored.
+    pub fn parse(text: &str) -> Recording {
+        let mut recording = Recording::new(Profile::Diagnostic);
+        for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with("#")) {
+            if let Some(name) = line.strip_prefix("profile ") {
+                let name = name.trim();
+                recording.profile = match Profile::parse(name) {
+                    None => panic!("Unknown instruction set profile \"{}\" in replay", name),
+                    Some(profile) => profile
+                };
+            } else {
+                recording.events.push(parse_event(line));
+            }
+        }
+        return recording;
+    }
+
+    pub fn inputs(&self) -> Vec<i32> {
+        return self.events.iter()
+                   .filter_map(|event| match event {
+                       Event::Input { value, .. } => Some(*value),
+                       Event::Output { .. } => None
+                   })
+                   .collect();
+    }
+
+    // The first place another run of the same program, given the same inputs, did something different
+    pub fn divergence(&self, replayed: &Recording) -> Option<Divergence> {
+        let length = self.events.len().max(replayed.events.len());
+        for index in 0..length {
+            let expected = self.events.get(index).cloned();
+            let actual = replayed.events.get(index).cloned();
+            if expected != actual {
+                return Some(Divergence {index, expected, actual});
+            }
+        }
+        return None;
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# Intcode replay: <in|out> <instructions executed before it> <value>")?;
+        writeln!(f, "profile {}", self.profile.name())?;
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        return Ok(());
+    }
+}
+
+fn parse_event(line: &str) -> Event {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (step, value) = match (fields.len(), fields.get(1).map(|step| step.parse()), fields.get(2).map(|value| value.parse())) {
+        (3, Some(Ok(step)), Some(Ok(value))) => (step, value),
+        _ => panic!("Couldn't parse replay event \"{}\"", line)
+    };
+
+    return match fields[0] {
+        "in" => Event::Input {step, value},
+        "out" => Event::Output {step, value},
+        kind => panic!("Unknown replay event \"{}\"", kind)
+    };
+}
+
+pub struct Divergence {
+    pub index: usize,
+    pub expected: Option<Event>,
+    pub actual: Option<Event>
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let describe = |event: &Option<Event>| match event {
+            None => String::from("nothing"),
+            Some(event) => event.to_string()
+        };
+        write!(f, "Event {} differs: expected {}, but got {}", self.index, describe(&self.expected), describe(&self.actual))
+    }
+}
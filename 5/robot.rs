@@ -0,0 +1,104 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::grid::{self, Direction, Point};
+use super::{ExecutionState, IntcodeComputer, IntcodeError, Memory};
+
+pub const BLACK: i32 = 0;
+pub const WHITE: i32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RobotError {
+    Failed(IntcodeError),
+    // A colour was output without a turn to go with it
+    MissingTurn { colour: i32 },
+    // A turn other than 0 (left) or 1 (right)
+    UnknownTurn(i32)
+}
+
+impl fmt::Display for RobotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RobotError::Failed(error) => write!(f, "{}", error),
+            RobotError::MissingTurn { colour } => write!(f, "Expected a colour and a turn from the robot program, but only got the colour {}!", colour),
+            RobotError::UnknownTurn(turn) => write!(f, "Unknown turn instruction from the robot program: {}!", turn)
+        }
+    }
+}
+
+// A robot on an infinite grid. It tells the program the colour of the cell it is on, and the
+// program answers with the colour to paint that cell followed by which way to turn (0 for left,
+// 1 for right) before moving forward one cell.
+pub struct Robot {
+    position: Point,
+    direction: Direction,
+    hull: HashMap<Point, i32>,
+    painted: HashSet<Point>
+}
+
+impl Robot {
+    pub fn new(starting_colour: i32) -> Robot {
+        let position = Point::new(0, 0);
+        let mut hull = HashMap::new();
+        hull.insert(position, starting_colour);
+
+        return Robot {
+            position,
+            direction: Direction::Up,
+            hull,
+            painted: HashSet::new()
+        };
+    }
+
+    pub fn colour(&self, point: Point) -> i32 {
+        return *self.hull.get(&point).unwrap_or(&BLACK);
+    }
+
+    pub fn painted_count(&self) -> usize {
+        return self.painted.len();
+    }
+
+    pub fn run(&mut self, computer: &IntcodeComputer, memory: &mut Memory) -> Result<(), RobotError> {
+        loop {
+            let colour = [self.colour(self.position)];
+            let mut output = Vec::new();
+            let state = match computer.resume(memory, &mut colour.iter(), &mut output) {
+                Err(why) => return Err(RobotError::Failed(why)),
+                Ok(state) => state
+            };
+
+            let instructions = output.chunks_exact(2);
+            if let Some(&colour) = instructions.remainder().first() {
+                return Err(RobotError::MissingTurn {colour});
+            }
+            for instruction in instructions {
+                self.paint(instruction[0]);
+                self.turn(instruction[1])?;
+                self.position = self.position + self.direction.delta();
+            }
+
+            if state == ExecutionState::Halted {
+                return Ok(());
+            }
+        }
+    }
+
+    fn paint(&mut self, colour: i32) {
+        self.hull.insert(self.position, colour);
+        self.painted.insert(self.position);
+    }
+
+    fn turn(&mut self, turn: i32) -> Result<(), RobotError> {
+        self.direction = match turn {
+            0 => self.direction.turn_left(),
+            1 => self.direction.turn_right(),
+            _ => return Err(RobotError::UnknownTurn(turn))
+        };
+        return Ok(());
+    }
+
+    pub fn render(&self) -> String {
+        let points: Vec<Point> = self.hull.keys().cloned().collect();
+        return grid::render(&points, |point| if self.colour(point) == WHITE { '#' } else { ' ' });
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::{ExecutionState, IntcodeComputer, IntcodeError, Memory, Program};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MachineState {
+    Ready,
+    // Waiting for input on its channel
+    Blocked,
+    Halted,
+    Failed(IntcodeError)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchedulerError {
+    // Every machine still running is waiting for input that can never come
+    Deadlock { blocked: Vec<usize> },
+    Failed { machine: usize, error: IntcodeError }
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchedulerError::Deadlock { blocked } => write!(f, "Deadlock: machines {:?} are all waiting for input!", blocked),
+            SchedulerError::Failed { machine, error } => write!(f, "Machine {} failed: {}", machine, error)
+        }
+    }
+}
+
+struct Machine {
+    memory: Memory,
+    // The machine's channel: input sent to it that it hasn't read yet
+    input: VecDeque<i32>,
+    // Everything it has output, whether or not that went anywhere
+    output: Vec<i32>,
+    // Machines whose channels its output is sent to
+    destinations: Vec<usize>,
+    state: MachineState
+}
+
+// Runs many programs on one thread, taking turns of at most quantum instructions each. A machine
+// that needs input nobody has sent yet is skipped until something is sent to it.
+pub struct Scheduler<'a> {
+    computer: &'a IntcodeComputer,
+    quantum: usize,
+    machines: Vec<Machine>
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn new(computer: &'a IntcodeComputer, quantum: usize) -> Scheduler<'a> {
+        return Scheduler {computer, quantum, machines: Vec::new()};
+    }
+
+    // Adds a machine running the program, returning its number
+    pub fn spawn(&mut self, program: &Program) -> usize {
+        self.machines.push(Machine {
+            memory: Memory::initialize(program),
+            input: VecDeque::new(),
+            output: Vec::new(),
+            destinations: Vec::new(),
+            state: MachineState::Ready
+        });
+        return self.machines.len() - 1;
+    }
+
+    // Sends everything from now on that one machine outputs to another
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.machines[from].destinations.push(to);
+    }
+
+    pub fn send(&mut self, machine: usize, value: i32) {
+        let machine = &mut self.machines[machine];
+        machine.input.push_back(value);
+        if machine.state == MachineState::Blocked {
+            machine.state = MachineState::Ready;
+        }
+    }
+
+    pub fn output(&self, machine: usize) -> &[i32] {
+        return &self.machines[machine].output;
+    }
+
+    // Gives the machine a turn, then sends whatever it output on to its destinations
+    fn run_turn(&mut self, index: usize) {
+        let machine = &mut self.machines[index];
+        let mut input = machine.input.iter();
+        let mut output = Vec::new();
+        let result = self.computer.resume_for(&mut machine.memory, &mut input, &mut output, self.quantum);
+        let consumed = machine.input.len() - input.len();
+        machine.input.drain(..consumed);
+
+        machine.state = match result {
+            Err(why) => MachineState::Failed(why),
+            Ok(ExecutionState::Halted) => MachineState::Halted,
+            Ok(ExecutionState::AwaitingInput) => MachineState::Blocked,
+            Ok(ExecutionState::Paused) => MachineState::Ready
+        };
+        machine.output.extend(&output);
+
+        for destination in machine.destinations.clone() {
+            for &value in &output {
+                self.send(destination, value);
+            }
+        }
+    }
+
+    // Takes turns round robin until every machine halts, one fails, or none can go on
+    pub fn run(&mut self) -> Result<(), SchedulerError> {
+        loop {
+            let mut ran = false;
+            for index in 0..self.machines.len() {
+                if self.machines[index].state != MachineState::Ready {
+                    continue;
+                }
+
+                self.run_turn(index);
+                ran = true;
+                if let MachineState::Failed(error) = &self.machines[index].state {
+                    return Err(SchedulerError::Failed {machine: index, error: error.clone()});
+                }
+            }
+
+            if !ran {
+                let blocked: Vec<usize> = (0..self.machines.len()).filter(|&index| self.machines[index].state == MachineState::Blocked).collect();
+                return if blocked.is_empty() { Ok(()) } else { Err(SchedulerError::Deadlock {blocked}) };
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// ANSI escapes to clear the terminal and move the cursor back to the top left corner
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+// Decodes (x, y, tile_id) triples from a program's output into a framebuffer
+pub struct Screen {
+    tiles: HashMap<(i32, i32), i32>,
+    glyphs: HashMap<i32, char>,
+    score_position: (i32, i32),
+    score: Option<i32>,
+    decoded_values: usize
+}
+
+impl Screen {
+    pub fn new(score_position: (i32, i32)) -> Screen {
+        let glyphs = vec![(0, ' '), (1, '#'), (2, '='), (3, '_'), (4, 'o')];
+
+        return Screen {
+            tiles: HashMap::new(),
+            glyphs: glyphs.into_iter().collect(),
+            score_position,
+            score: None,
+            decoded_values: 0
+        };
+    }
+
+    pub fn set_glyph(&mut self, tile: i32, glyph: char) {
+        self.glyphs.insert(tile, glyph);
+    }
+
+    pub fn score(&self) -> Option<i32> {
+        return self.score;
+    }
+
+    pub fn tile(&self, x: i32, y: i32) -> i32 {
+        return *self.tiles.get(&(x, y)).unwrap_or(&0);
+    }
+
+    pub fn count_tiles(&self, tile: i32) -> usize {
+        return self.tiles.values().filter(|&&value| value == tile).count();
+    }
+
+    // Decodes any complete triples that haven't been seen yet, so the same (growing) output vector
+    // can be passed in repeatedly. Returns true if anything on the screen changed.
+    pub fn update(&mut self, output: &[i32]) -> bool {
+        let mut changed = false;
+
+        while self.decoded_values + 3 <= output.len() {
+            let x = output[self.decoded_values];
+            let y = output[self.decoded_values + 1];
+            let value = output[self.decoded_values + 2];
+            self.decoded_values += 3;
+
+            changed |= self.set_tile(x, y, value);
+        }
+
+        return changed;
+    }
+
+    // Returns true if the tile (or score) changed
+    pub fn set_tile(&mut self, x: i32, y: i32, value: i32) -> bool {
+        if (x, y) == self.score_position {
+            let changed = self.score != Some(value);
+            self.score = Some(value);
+            return changed;
+        }
+
+        return self.tiles.insert((x, y), value) != Some(value);
+    }
+
+    pub fn render(&self) -> String {
+        let mut frame = String::new();
+        if let Some(score) = self.score {
+            frame.push_str(&format!("Score: {}\n", score));
+        }
+
+        if self.tiles.is_empty() {
+            return frame;
+        }
+
+        let min_x = self.tiles.keys().map(|&(x, _)| x).min().unwrap();
+        let max_x = self.tiles.keys().map(|&(x, _)| x).max().unwrap();
+        let min_y = self.tiles.keys().map(|&(_, y)| y).min().unwrap();
+        let max_y = self.tiles.keys().map(|&(_, y)| y).max().unwrap();
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                frame.push(*self.glyphs.get(&self.tile(x, y)).unwrap_or(&'?'));
+            }
+            frame.push('\n');
+        }
+
+        return frame;
+    }
+
+    pub fn draw(&self, terminal: &mut dyn Write) -> io::Result<()> {
+        write!(terminal, "{}{}", CLEAR_SCREEN, self.render())?;
+        return terminal.flush();
+    }
+
+    // Decodes a whole output vector without touching the terminal and returns the final frame
+    pub fn render_headless(mut self, output: &[i32]) -> String {
+        self.update(output);
+        return self.render();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Screen;
+
+    #[test]
+    fn headless_render_draws_the_last_tile_at_each_position() {
+        let output = vec![
+            0, 0, 1, 1, 0, 1, 2, 0, 1,
+            0, 1, 1, 1, 1, 2, 2, 1, 1,
+            1, 1, 4, -1, 0, 42, 2, 1, 0
+        ];
+        assert_eq!(Screen::new((-1, 0)).render_headless(&output), "Score: 42\n###\n#o \n");
+    }
+
+    #[test]
+    fn headless_render_ignores_an_incomplete_triple() {
+        let mut screen = Screen::new((-1, 0));
+        screen.set_glyph(3, '-');
+        assert_eq!(screen.render_headless(&[0, 0, 3, 1, 0]), "-\n");
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::intcode::{Instruction, Observer, Verdict};
+use super::Memory;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    // Note every patch and keep running
+    Warn,
+    // Stop the program as soon as a patch is found
+    Trap
+}
+
+impl Policy {
+    pub fn parse(name: &str) -> Option<Policy> {
+        return match name {
+            "warn" => Some(Policy::Warn),
+            "trap" => Some(Policy::Trap),
+            _ => None
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum When {
+    // The cell had already been run as part of an instruction when it was written
+    AfterExecution,
+    // The cell was written first, and run as part of an instruction later
+    BeforeExecution { executed_at: usize }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Patch {
+    // How many instructions had run before the one doing the write
+    pub step: usize,
+    pub writer: usize,
+    pub target: usize,
+    pub old: i32,
+    pub new: i32,
+    pub when: When
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Step {}: instruction at {} changed {} from {} to {}", self.step, self.writer, self.target, self.old, self.new)?;
+        match self.when {
+            When::AfterExecution => write!(f, ", which had already been run"),
+            When::BeforeExecution { executed_at } => write!(f, ", which was then run by the instruction at {}", executed_at)
+        }
+    }
+}
+
+// Finds instructions that write over code, whether that code has been run already or is run later
+pub struct SelfModificationDetector {
+    policy: Policy,
+    step: usize,
+    // Every cell run as an opcode or operand so far
+    executed: BTreeSet<usize>,
+    // Writes to cells that haven't been run yet, in case they are later
+    written: BTreeMap<usize, Patch>,
+    pub patches: Vec<Patch>
+}
+
+impl SelfModificationDetector {
+    pub fn new(policy: Policy) -> SelfModificationDetector {
+        return SelfModificationDetector {policy, step: 0, executed: BTreeSet::new(), written: BTreeMap::new(), patches: Vec::new()};
+    }
+
+    fn found(&mut self, patch: Patch) -> Verdict {
+        self.patches.push(patch);
+        return match self.policy {
+            Policy::Warn => Verdict::Continue,
+            Policy::Trap => Verdict::Abort
+        };
+    }
+}
+
+impl Observer for SelfModificationDetector {
+    fn before_instruction(&mut self, memory: &Memory, instruction: &Instruction) -> Verdict {
+        self.step = memory.steps;
+
+        let mut verdict = Verdict::Continue;
+        let cells = memory.program_counter..=memory.program_counter + instruction.parameter_count as usize;
+        for cell in cells {
+            if let Some(mut patch) = self.written.remove(&cell) {
+                patch.when = When::BeforeExecution {executed_at: memory.program_counter};
+                verdict = verdict.max(self.found(patch));
+            }
+            // Marked now so an instruction overwriting its own operands counts as patching code that ran
+            self.executed.insert(cell);
+        }
+        return verdict;
+    }
+
+    fn memory_write(&mut self, pc: usize, address: usize, old: i32, new: i32) -> Verdict {
+        // Writes of the same value change nothing, which programs do a lot when reusing scratch cells
+        if old == new {
+            return Verdict::Continue;
+        }
+
+        let patch = Patch {step: self.step, writer: pc, target: address, old, new, when: When::AfterExecution};
+        if self.executed.contains(&address) {
+            return self.found(patch);
+        }
+
+        // Only the last write before the cell is run matters, but keep the first old value
+        let patch = match self.written.get(&address) {
+            Some(earlier) => Patch {old: earlier.old, ..patch},
+            None => patch
+        };
+        self.written.insert(address, patch);
+        return Verdict::Continue;
+    }
+}
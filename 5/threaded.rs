@@ -0,0 +1,126 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use super::{ExecutionState, IntcodeComputer, IntcodeError, Memory, Program};
+
+// How many instructions run between sending output on, so a program that never asks for input
+// still gets its output to its peers
+const QUANTUM: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeError {
+    Failed(IntcodeError),
+    // Waiting for input when every machine that could send it had halted
+    InputClosed,
+    TimedOut(Duration)
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::Failed(why) => write!(f, "{}", why),
+            RuntimeError::InputClosed => write!(f, "Waiting for input, but everything that could send it has halted!"),
+            RuntimeError::TimedOut(timeout) => write!(f, "No input came within {:?}!", timeout)
+        }
+    }
+}
+
+struct Machine {
+    program: Program,
+    sender: Sender<i32>,
+    receiver: Receiver<i32>,
+    destinations: Vec<Sender<i32>>
+}
+
+// Runs each program on its own thread, with input instructions blocking until something arrives on
+// the machine's channel. A machine's channel closes once everything that can send on it has halted,
+// so machines waiting on halted peers stop instead of hanging. The threads are scoped so they can
+// borrow the computer, which needs Rust 1.63 or later.
+pub struct Runtime<'a> {
+    computer: &'a IntcodeComputer,
+    timeout: Duration,
+    machines: Vec<Machine>
+}
+
+impl<'a> Runtime<'a> {
+    pub fn new(computer: &'a IntcodeComputer, timeout: Duration) -> Runtime<'a> {
+        return Runtime {computer, timeout, machines: Vec::new()};
+    }
+
+    // Adds a machine running the program, returning its number
+    pub fn spawn(&mut self, program: &Program) -> usize {
+        let (sender, receiver) = mpsc::channel();
+        self.machines.push(Machine {program: program.clone(), sender, receiver, destinations: Vec::new()});
+        return self.machines.len() - 1;
+    }
+
+    // Sends everything one machine outputs to another
+    pub fn connect(&mut self, from: usize, to: usize) {
+        let sender = self.machines[to].sender.clone();
+        self.machines[from].destinations.push(sender);
+    }
+
+    // Queues input for a machine to read once it starts
+    pub fn send(&mut self, machine: usize, value: i32) {
+        // The receiver is still here, so this can't fail
+        self.machines[machine].sender.send(value).unwrap();
+    }
+
+    // Runs every machine until it halts or fails, returning what each output
+    pub fn run(self) -> Vec<Result<Vec<i32>, RuntimeError>> {
+        let computer = self.computer;
+        let timeout = self.timeout;
+        return thread::scope(|scope| {
+            let handles: Vec<_> = self.machines.into_iter()
+                                      .map(|machine| {
+                                          let Machine {program, sender, receiver, destinations} = machine;
+                                          // Only peers should keep the channel open
+                                          drop(sender);
+                                          scope.spawn(move || run_machine(computer, &program, receiver, destinations, timeout))
+                                      })
+                                      .collect();
+
+            return handles.into_iter()
+                          .enumerate()
+                          .map(|(index, handle)| match handle.join() {
+                              Err(_) => panic!("Machine {} panicked", index),
+                              Ok(result) => result
+                          })
+                          .collect();
+        });
+    }
+}
+
+fn run_machine(computer: &IntcodeComputer, program: &Program, receiver: Receiver<i32>, destinations: Vec<Sender<i32>>, timeout: Duration) -> Result<Vec<i32>, RuntimeError> {
+    let mut memory = Memory::initialize(program);
+    let mut input: Vec<i32> = Vec::new();
+    let mut all_output = Vec::new();
+
+    loop {
+        let mut output = Vec::new();
+        let mut remaining = input.iter();
+        let state = computer.resume_for(&mut memory, &mut remaining, &mut output, QUANTUM);
+        input = remaining.cloned().collect();
+
+        for &value in &output {
+            for destination in &destinations {
+                // A peer that has halted doesn't need it
+                let _ = destination.send(value);
+            }
+        }
+        all_output.extend(output);
+
+        match state {
+            Err(why) => return Err(RuntimeError::Failed(why)),
+            Ok(ExecutionState::Halted) => return Ok(all_output),
+            Ok(ExecutionState::Paused) => (),
+            Ok(ExecutionState::AwaitingInput) => match receiver.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout) => return Err(RuntimeError::TimedOut(timeout)),
+                Err(RecvTimeoutError::Disconnected) => return Err(RuntimeError::InputClosed),
+                Ok(value) => input.push(value)
+            }
+        };
+    }
+}
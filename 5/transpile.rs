@@ -0,0 +1,191 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::disassembly::{self, ControlFlow, DecodedInstruction, Operand};
+use super::{IntcodeComputer, Program};
+
+pub struct Transpiled {
+    pub source: String,
+    // False if the program modifies its own code, so the generated function interprets it instead
+    pub compiled: bool
+}
+
+// Turns a program into a standalone Rust function:
+//
+//     fn name(input: &mut dyn Iterator<Item = i32>, output: &mut Vec<i32>) -> Vec<i32>
+//
+// which runs the program and returns its final memory. Memory behaves like the computer's: reading
+// past the end gives 0, and writing past the end grows it. Programs whose code can be fully worked
+// out up front become one match arm per basic block; anything else gets a small interpreter.
+pub fn transpile(computer: &IntcodeComputer, program: &Program, function_name: &str) -> Transpiled {
+    let control_flow = disassembly::trace_control_flow(computer, program);
+    let compiled = control_flow.is_complete();
+
+    let mut source = String::new();
+    // Programs that never read input, or never read or write memory, would otherwise generate warnings
+    writeln!(source, "#[allow(dead_code, unused_mut, unused_variables)]").unwrap();
+    writeln!(source, "pub fn {}(input: &mut dyn Iterator<Item = i32>, output: &mut Vec<i32>) -> Vec<i32> {{", function_name).unwrap();
+    writeln!(source, "    let mut memory: Vec<i32> = vec![{}];", super::format_program(program)).unwrap();
+    source.push_str(MEMORY);
+
+    if compiled {
+        write_blocks(&mut source, &control_flow);
+    } else {
+        write_interpreter(&mut source);
+    }
+
+    writeln!(source, "}}").unwrap();
+    return Transpiled {source, compiled};
+}
+
+// Reading and writing memory the way the computer does, shared by compiled and interpreted programs
+const MEMORY: &str = "    fn read(memory: &[i32], address: usize) -> i32 {
+        return memory.get(address).cloned().unwrap_or(0);
+    }
+
+    fn write(memory: &mut Vec<i32>, address: usize, value: i32) {
+        if address >= memory.len() {
+            memory.resize(address + 1, 0);
+        }
+        memory[address] = value;
+    }
+
+";
+
+fn operand(value: &Operand) -> String {
+    return match value {
+        Operand::Immediate(value) => value.to_string(),
+        Operand::Position(address) => format!("read(&memory, {})", address)
+    };
+}
+
+// Jumping to the jump itself leaves the pc where it was, so the computer moves on to the next instruction
+fn jump(instruction: &DecodedInstruction) -> String {
+    let (address, next) = (instruction.address, instruction.next_address());
+    return match instruction.operands[1] {
+        Operand::Immediate(target) if target < 0 => format!("panic!(\"Jumped to {}\")", target),
+        Operand::Immediate(target) if target as usize == address => format!("pc = {}", next),
+        Operand::Immediate(target) => format!("pc = {}", target),
+        Operand::Position(cell) => format!("let target = read(&memory, {}) as usize; pc = if target == {} {{ {} }} else {{ target }}", cell, address, next)
+    };
+}
+
+// The Rust statement equivalent to a (non jump) instruction
+fn statement(instruction: &DecodedInstruction) -> String {
+    let operands: Vec<String> = instruction.operands.iter().map(operand).collect();
+    // Write operands are always position mode, so this is where they point
+    let destination = match instruction.write_targets().first() {
+        None => String::new(),
+        Some(address) => address.to_string()
+    };
+
+    return match instruction.opcode {
+        1 => format!("let value = {} + {}; write(&mut memory, {}, value);", operands[0], operands[1], destination),
+        2 => format!("let value = {} * {}; write(&mut memory, {}, value);", operands[0], operands[1], destination),
+        3 => format!("let value = input.next().expect(\"Ran out of input!\"); write(&mut memory, {}, value);", destination),
+        4 => format!("output.push({});", operands[0]),
+        7 => format!("let value = if {} < {} {{ 1 }} else {{ 0 }}; write(&mut memory, {}, value);", operands[0], operands[1], destination),
+        8 => format!("let value = if {} == {} {{ 1 }} else {{ 0 }}; write(&mut memory, {}, value);", operands[0], operands[1], destination),
+        opcode => format!("panic!(\"Missing instruction definition for opcode {}!\");", opcode)
+    };
+}
+
+fn write_blocks(source: &mut String, control_flow: &ControlFlow) {
+    // Blocks start at the entry point, every jump target, and just after every jump
+    let mut leaders: BTreeSet<usize> = control_flow.jump_targets.keys()
+                                                   .filter(|target| control_flow.instructions.contains_key(target))
+                                                   .cloned()
+                                                   .collect();
+    leaders.insert(0);
+    for instruction in control_flow.instructions.values().filter(|instruction| instruction.is_jump()) {
+        leaders.insert(instruction.next_address());
+    }
+
+    writeln!(source, "    let mut pc: usize = 0;").unwrap();
+    writeln!(source, "    loop {{").unwrap();
+    writeln!(source, "        match pc {{").unwrap();
+
+    for &leader in leaders.iter().filter(|leader| control_flow.instructions.contains_key(leader)) {
+        writeln!(source, "            {} => {{", leader).unwrap();
+
+        let mut address = leader;
+        loop {
+            let instruction = match control_flow.instructions.get(&address) {
+                None => {
+                    writeln!(source, "                panic!(\"No instruction at {}\");", address).unwrap();
+                    break;
+                },
+                Some(instruction) => instruction
+            };
+
+            if instruction.is_halt() {
+                writeln!(source, "                return memory;").unwrap();
+                break;
+            }
+
+            if instruction.is_jump() {
+                let value = operand(&instruction.operands[0]);
+                let condition = if instruction.opcode == 5 { format!("{} != 0", value) } else { format!("{} == 0", value) };
+                let target = jump(instruction);
+
+                match instruction.constant_condition() {
+                    Some(true) => writeln!(source, "                {};", target).unwrap(),
+                    Some(false) => writeln!(source, "                pc = {};", instruction.next_address()).unwrap(),
+                    None => {
+                        writeln!(source, "                if {} {{ {}; continue; }}", condition, target).unwrap();
+                        writeln!(source, "                pc = {};", instruction.next_address()).unwrap();
+                    }
+                };
+                break;
+            }
+
+            writeln!(source, "                {}", statement(instruction)).unwrap();
+            address = instruction.next_address();
+            if leaders.contains(&address) {
+                writeln!(source, "                pc = {};", address).unwrap();
+                break;
+            }
+        }
+
+        writeln!(source, "            }},").unwrap();
+    }
+
+    writeln!(source, "            _ => panic!(\"Jumped to {{}}, which isn't the start of an instruction\", pc)").unwrap();
+    writeln!(source, "        }}").unwrap();
+    writeln!(source, "    }}").unwrap();
+}
+
+const INTERPRETER: &str = "    // This program writes over its own code, so it is interpreted rather than compiled
+    fn parameter(memory: &[i32], pc: usize, index: u32) -> i32 {
+        let parameter = read(memory, pc + index as usize);
+        return match read(memory, pc) / 10i32.pow(index + 1) % 10 {
+            0 => read(memory, parameter as usize),
+            1 => parameter,
+            mode => panic!(\"Unknown parameter mode: {}!\", mode)
+        };
+    }
+
+    let mut pc: usize = 0;
+    while read(&memory, pc) != 99 {
+        let a = |memory: &[i32]| parameter(memory, pc, 1);
+        let b = |memory: &[i32]| parameter(memory, pc, 2);
+        let destination = |memory: &[i32], index: usize| read(memory, pc + index) as usize;
+        match read(&memory, pc) % 100 {
+            1 => { let value = a(&memory) + b(&memory); let target = destination(&memory, 3); write(&mut memory, target, value); pc += 4; },
+            2 => { let value = a(&memory) * b(&memory); let target = destination(&memory, 3); write(&mut memory, target, value); pc += 4; },
+            3 => { let value = input.next().expect(\"Ran out of input!\"); let target = destination(&memory, 1); write(&mut memory, target, value); pc += 2; },
+            4 => { output.push(a(&memory)); pc += 2; },
+            5 => { let target = b(&memory) as usize; pc = if a(&memory) != 0 && target != pc { target } else { pc + 3 }; },
+            6 => { let target = b(&memory) as usize; pc = if a(&memory) == 0 && target != pc { target } else { pc + 3 }; },
+            7 => { let value = if a(&memory) < b(&memory) { 1 } else { 0 }; let target = destination(&memory, 3); write(&mut memory, target, value); pc += 4; },
+            8 => { let value = if a(&memory) == b(&memory) { 1 } else { 0 }; let target = destination(&memory, 3); write(&mut memory, target, value); pc += 4; },
+            opcode => panic!(\"Missing instruction definition for opcode {}!\", opcode)
+        };
+    }
+
+    return memory;
+";
+
+fn write_interpreter(source: &mut String) {
+    source.push_str(INTERPRETER);
+}
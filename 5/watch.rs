@@ -0,0 +1,43 @@
+use super::intcode::{Instruction, Observer, Verdict};
+use super::Memory;
+
+// Pauses whenever something is written to the watched cell
+pub struct Watchpoint {
+    address: usize,
+    // The pc of the instruction that wrote, and the value before and after, for each write
+    pub writes: Vec<(usize, i32, i32)>
+}
+
+impl Watchpoint {
+    pub fn new(address: usize) -> Watchpoint {
+        return Watchpoint {address, writes: Vec::new()};
+    }
+}
+
+impl Observer for Watchpoint {
+    fn memory_write(&mut self, pc: usize, address: usize, old: i32, new: i32) -> Verdict {
+        if address != self.address {
+            return Verdict::Continue;
+        }
+
+        self.writes.push((pc, old, new));
+        return Verdict::Pause;
+    }
+}
+
+// Aborts once the program has run this many instructions, for programs that might never halt
+pub struct StepLimit {
+    limit: usize
+}
+
+impl StepLimit {
+    pub fn new(limit: usize) -> StepLimit {
+        return StepLimit {limit};
+    }
+}
+
+impl Observer for StepLimit {
+    fn before_instruction(&mut self, memory: &Memory, _instruction: &Instruction) -> Verdict {
+        return if memory.steps >= self.limit { Verdict::Abort } else { Verdict::Continue };
+    }
+}